@@ -10,6 +10,125 @@ mod httpusd {
     use ink::prelude::vec::Vec;
     use ink::storage::Mapping;
 
+    /// Maximum length in bytes of an authorization nonce
+    pub const MAX_NONCE_LEN: usize = 128;
+
+    /// Length of a spend-limit day bucket in milliseconds
+    pub const DAY_MS: u64 = 86_400_000;
+
+    /// Default window during which an idempotency key replays its recorded result
+    pub const DEFAULT_IDEMPOTENCY_WINDOW_MS: u64 = 3_600_000;
+
+    /// Number of fee change records retained for `fee_bps_at`
+    pub const FEE_HISTORY_CAPACITY: u32 = 32;
+
+    /// Number of settlements retained for `recent_payments`
+    pub const PAYMENT_HISTORY_CAPACITY: u32 = 64;
+
+    /// Decimals used when none are given at construction
+    pub const DEFAULT_DECIMALS: u8 = 12;
+
+    /// Maximum length in bytes of the token name and symbol
+    pub const MAX_METADATA_LEN: usize = 64;
+
+    /// Facilitator fee cap used by constructors that don't set one (10%)
+    pub const DEFAULT_MAX_FEE_BPS: u16 = 1_000;
+
+    /// Personalization string hashed into the domain separator
+    pub const DOMAIN_PERSONALIZATION: &[u8] = b"polkax402:httpusd";
+
+    /// Ref-time budget of one payment hook call
+    pub const PAYMENT_HOOK_REF_TIME_LIMIT: u64 = 5_000_000_000;
+
+    /// Proof-size budget of one payment hook call
+    pub const PAYMENT_HOOK_PROOF_SIZE_LIMIT: u64 = 256 * 1024;
+
+    /// Ref-time kept back for finishing the settlement after a payment hook call
+    pub const PAYMENT_HOOK_GAS_RESERVE: u64 = 1_000_000_000;
+
+    /// Maximum number of signers in a multisig payer configuration
+    pub const MAX_MULTISIG_SIGNERS: usize = 16;
+
+    /// Maximum number of `(from, nonce)` pairs answered by one `are_nonces_used` call
+    pub const MAX_NONCE_QUERIES: usize = 256;
+
+    /// Maximum number of accounts answered by one `balances_of` call
+    pub const MAX_BALANCE_QUERIES: usize = 256;
+
+    /// Maximum number of authorizations settled by one `transfer_with_authorization_batch` call
+    pub const MAX_BATCH_SIZE: usize = 64;
+
+    /// Tag prefixed to the nonce hash and signed message of bytes32 nonces
+    pub const B32_NONCE_TAG: &[u8] = b"polkax402:b32";
+
+    /// Tag prefixed to the nonce hash and signed message of recipient-scoped nonces
+    pub const SCOPED_NONCE_TAG: &[u8] = b"polkax402:scoped";
+
+    /// Prefix of messages signed for `transfer_with_authorization_v2`
+    pub const PAYMENT_V2_PREFIX: &[u8] = b"polkax402:v2";
+
+    /// Prefix of merchant-signed refund messages
+    pub const REFUND_MESSAGE_PREFIX: &[u8] = b"polkax402:refund";
+
+    /// Prefix of split payment messages
+    pub const SPLIT_MESSAGE_PREFIX: &[u8] = b"polkax402:split";
+
+    /// Tag prefixed to the nonce hash and signed message of signed approvals
+    pub const APPROVE_MESSAGE_PREFIX: &[u8] = b"polkax402:approve";
+
+    /// Prefix of messages signed for `transfer_with_authorization_partial`
+    pub const PARTIAL_MESSAGE_PREFIX: &[u8] = b"polkax402:partial";
+
+    /// Maximum number of recipients of a split payment
+    pub const MAX_SPLIT_RECIPIENTS: usize = 16;
+
+    /// Order of the Ristretto255 group (little-endian); canonical sr25519
+    /// signature scalars are below it
+    const RISTRETTO_GROUP_ORDER: [u8; 32] = [
+        0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde,
+        0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x10,
+    ];
+
+    /// Version of the domain separator / signed message layout
+    pub const DOMAIN_VERSION: u8 = 1;
+
+    /// Message version byte of `version ++ payment_message` preimages; the only
+    /// version accepted by default
+    pub const MESSAGE_VERSION_1: u8 = 1;
+
+    /// Semantic version `(major, minor, patch)` of the contract logic
+    pub const CONTRACT_VERSION: (u16, u16, u16) = (0, 1, 0);
+
+    /// `signature_backend` value: verification uses the host functions
+    /// (`sr25519_verify`, `ecdsa_recover`)
+    pub const SIGNATURE_BACKEND_HOST: u8 = 0;
+
+    /// `expiry_semantics` kind: `valid_until` is a block timestamp in milliseconds
+    pub const EXPIRY_KIND_TIMESTAMP: u8 = 0;
+
+    /// Role allowed to grant and revoke roles
+    pub const ROLE_ADMIN: u8 = 0;
+
+    /// Role allowed to change the facilitator fee, fee recipient and late surcharge
+    pub const ROLE_FEE_MANAGER: u8 = 1;
+
+    /// Role allowed to pause and unpause the contract and authorizations
+    pub const ROLE_PAUSER: u8 = 2;
+
+    /// Role allowed to manage the blocklist
+    pub const ROLE_BLOCKLIST_MANAGER: u8 = 3;
+
+    /// Every role, held by the owner at construction and on ownership transfer
+    const ALL_ROLES: [u8; 4] = [ROLE_ADMIN, ROLE_FEE_MANAGER, ROLE_PAUSER, ROLE_BLOCKLIST_MANAGER];
+
+    /// Relative verification cost of an sr25519 authorization
+    pub const SR25519_COST_HINT: u32 = 100;
+
+    /// Relative verification cost of an ECDSA authorization (Keccak256 +
+    /// public key recovery + Blake2x256 account derivation)
+    pub const ECDSA_COST_HINT: u32 = 160;
+
     /// PSP22 error types
     #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
@@ -20,6 +139,8 @@ mod httpusd {
         InsufficientAllowance,
         ZeroRecipientAddress,
         ZeroSenderAddress,
+        /// Arithmetic overflow in a balance, allowance or supply
+        Overflow,
     }
 
     /// X402-specific errors
@@ -35,8 +156,79 @@ mod httpusd {
         PaymentExpired,
         /// Nonce has already been used (replay attack)
         NonceAlreadyUsed,
+        /// Nonce is empty or longer than MAX_NONCE_LEN
+        InvalidNonce,
         /// Transfer failed
         TransferFailed,
+        /// Nonce has not been used
+        NonceNotFound,
+        /// Nonce belongs to an authorization that has not expired yet
+        NonceNotExpired,
+        /// Nonce hash prefix is not a registered namespace
+        UnknownNamespace,
+        /// Signature-based transfers are paused
+        AuthorizationsPaused,
+        /// Contract is paused (emergency stop)
+        ContractPaused,
+        /// Caller is not the pending owner
+        NotPendingOwner,
+        /// The owner may not authorize payments from its own account
+        OwnerSelfAuthorization,
+        /// Payment would exceed the payer's daily spend limit
+        SpendLimitExceeded,
+        /// Idempotency key was already used for a different authorization
+        IdempotencyKeyConflict,
+        /// Partial draw exceeds the authorization's remaining amount
+        DrawExceedsRemaining,
+        /// Fee recipient is the zero account
+        InvalidFeeRecipient,
+        /// Daily spend limit is set to zero, blocking every authorization
+        InvalidSpendLimit,
+        /// Caller is not the recipient of the payment being refunded
+        CallerNotRecipient,
+        /// Sender or recipient is on the blocklist
+        AccountBlocked,
+        /// Minting would take total supply above `max_supply`
+        MaxSupplyExceeded,
+        /// Authorization stays valid longer than `max_validity_window_ms`
+        ValidityTooLong,
+        /// Caller lacks the role required for this message
+        MissingRole,
+        /// More than `max_submission_delay_ms` passed since the signed `issued_at`
+        SubmissionTooLate,
+        /// Multisig signer set is empty, too large, has duplicates or an
+        /// unreachable threshold
+        InvalidMultisigConfig,
+        /// Payer has no multisig configuration
+        NotMultisig,
+        /// Fewer distinct authorized signers signed than the threshold requires
+        ThresholdNotMet,
+        /// The runtime rejected the new code hash
+        CodeUpgradeFailed,
+        /// Pulling the facilitator fee from the payer on `fee_token` failed
+        FeeTokenTransferFailed,
+        /// Caller is not the contract owner
+        NotOwner,
+        /// Authorized amount is below `min_amount`
+        AmountTooSmall,
+        /// Signed message version is not in the accepted set
+        UnsupportedMessageVersion,
+        /// Split recipient list is empty, too long or repeats a recipient
+        InvalidSplit,
+        /// Split shares do not add up to the authorized amount
+        SplitAmountMismatch,
+        /// Amount exceeds total supply, so no balance can cover it
+        AmountExceedsSupply,
+        /// Token name or symbol exceeds MAX_METADATA_LEN
+        MetadataTooLong,
+        /// Facilitator fee exceeds the configured cap
+        FeeTooHigh,
+        /// Refund exceeds what the recipient received and has not yet refunded
+        RefundExceedsPayment,
+        /// Batched view called with more queries than it answers at once
+        TooManyQueries,
+        /// Batch holds more than `MAX_BATCH_SIZE` authorizations
+        TooManyAuthorizations,
     }
 
     impl From<PSP22Error> for Error {
@@ -47,6 +239,110 @@ mod httpusd {
 
     pub type Result<T> = core::result::Result<T, Error>;
 
+    /// Signature scheme used to sign an X402 authorization
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum SignatureScheme {
+        /// 64-byte sr25519 signature over the Blake2x256 message hash
+        Sr25519,
+        /// 65-byte (r, s, v) secp256k1 signature over the Keccak256 message hash
+        Ecdsa,
+    }
+
+    /// How signed messages are framed before hashing
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum SigningFormat {
+        /// The message is hashed as is
+        Raw,
+        /// The message is wrapped in `<Bytes>`/`</Bytes>`, as done by the
+        /// Polkadot.js extension's `signRaw`
+        WrappedBytes,
+    }
+
+    /// How a fractional facilitator fee is rounded to a whole balance unit
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum FeeRounding {
+        /// Truncate, in the payer's favour
+        Down,
+        /// Any fraction rounds up, in the facilitator's favour
+        Up,
+        /// Fractions of one half or more round up
+        HalfUp,
+    }
+
+    /// Fee split of an authorized amount, computed before any state changes
+    #[derive(Clone, Copy)]
+    struct Settlement {
+        facilitator_fee: Balance,
+        net_amount: Balance,
+        /// Payer's spend for the current day including this settlement
+        spent_today: Balance,
+        /// How far past `valid_until` a late settlement was submitted
+        late_by_ms: Option<u64>,
+    }
+
+    /// A signed X402 payment authorization
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Authorization {
+        pub from: AccountId,
+        pub to: AccountId,
+        pub amount: Balance,
+        pub valid_until: u64,
+        pub nonce: String,
+        pub signature: Vec<u8>,
+        pub scheme: SignatureScheme,
+    }
+
+    /// A settled authorization, as retained for `recent_payments`
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct PaymentRecord {
+        pub from: AccountId,
+        pub to: AccountId,
+        pub net_amount: Balance,
+        pub fee: Balance,
+        pub nonce_hash: [u8; 32],
+        pub block_number: BlockNumber,
+    }
+
+    /// Snapshot of the admin-configurable settings, returned by `get_config`
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct ContractConfig {
+        pub owner: AccountId,
+        pub facilitator_fee_bps: u16,
+        pub fee_rounding: FeeRounding,
+        pub fee_recipient: AccountId,
+        pub paused: bool,
+        pub max_fee_bps: u16,
+        pub decimals: u8,
+        pub authorizations_paused: bool,
+        pub fee_token: Option<AccountId>,
+        pub expiry_grace_ms: u64,
+        pub min_amount: Balance,
+        pub min_fee: Balance,
+        pub chain_id: u32,
+        pub allow_no_expiry: bool,
+        pub namespace_gating: bool,
+        pub owner_cannot_self_authorize: bool,
+        pub signing_format: SigningFormat,
+        pub spend_limit_per_day: Option<Balance>,
+        pub max_validity_window_ms: Option<u64>,
+        pub max_submission_delay_ms: Option<u64>,
+        pub late_settlement_surcharge_bps: u16,
+        pub allow_late_within_ms: u64,
+        pub fee_timelock_ms: u64,
+        pub pending_fee: Option<(u16, u64)>,
+        pub pending_fee_timelock: Option<(u64, u64)>,
+        pub pending_min_fee: Option<(Balance, u64)>,
+        pub cancel_on_freeze: bool,
+        pub idempotency_window_ms: u64,
+        pub max_supply: Option<Balance>,
+    }
+
     /// httpusd Storage
     #[ink(storage)]
     pub struct Httpusd {
@@ -56,12 +352,141 @@ mod httpusd {
         balances: Mapping<AccountId, Balance>,
         /// Allowances for PSP22 transfers
         allowances: Mapping<(AccountId, AccountId), Balance>,
-        /// Used nonces for X402 (prevents replay attacks)
-        used_nonces: Mapping<[u8; 32], bool>,
+        /// Used nonces for X402 (prevents replay attacks), mapped to the
+        /// `valid_until` of the authorization that consumed them
+        used_nonces: Mapping<[u8; 32], u64>,
         /// Contract owner
         owner: AccountId,
+        /// Owner nominated by `transfer_ownership`, awaiting acceptance
+        pending_owner: Option<AccountId>,
         /// Facilitator fee in basis points (e.g., 100 = 1%)
         facilitator_fee_bps: u16,
+        /// Upper bound for `facilitator_fee_bps`
+        max_fee_bps: u16,
+        /// Account that receives facilitator fees
+        fee_recipient: AccountId,
+        /// Token name (PSP22Metadata)
+        token_name: Option<String>,
+        /// Token symbol (PSP22Metadata)
+        token_symbol: Option<String>,
+        /// Token decimals (PSP22Metadata)
+        decimals: u8,
+        /// Treat `valid_until == 0` as "never expires"
+        allow_no_expiry: bool,
+        /// Require nonce hashes to start with a registered namespace byte
+        namespace_gating: bool,
+        /// Registered nonce namespaces (first byte of the nonce hash)
+        nonce_namespaces: Mapping<u8, bool>,
+        /// Chain identifier bound into every signed authorization
+        chain_id: u32,
+        /// Domain separator derived at construction from the chain id and contract address
+        domain_separator: [u8; 32],
+        /// Pauses signature-based transfers only; PSP22 transfers stay live
+        authorizations_paused: bool,
+        /// Emergency stop for all settlements
+        paused: bool,
+        /// Number of accounts currently holding a nonzero balance
+        unique_holders: u64,
+        /// Block of the last authorization settled by or to an account
+        last_settled_block: Mapping<AccountId, BlockNumber>,
+        /// Reject authorizations signed by the owner's own account
+        owner_cannot_self_authorize: bool,
+        /// Maximum amount a payer may settle per day (None = unlimited)
+        spend_limit_per_day: Option<Balance>,
+        /// Amount settled per (payer, day bucket), day = block_timestamp / DAY_MS
+        daily_spent: Mapping<(AccountId, u64), Balance>,
+        /// Settled idempotency keys: key hash -> (nonce hash, settled at)
+        idempotency_records: Mapping<[u8; 32], ([u8; 32], u64)>,
+        /// How long an idempotency key keeps returning its recorded result
+        idempotency_window_ms: u64,
+        /// Remaining capacity of partially drawn authorizations, by nonce hash
+        partial_remaining: Mapping<[u8; 32], Balance>,
+        /// Accounts barred from sending or receiving authorized payments
+        blocklist: Mapping<AccountId, bool>,
+        /// Ring of `(timestamp, fee_bps)` change records, by slot
+        fee_history: Mapping<u32, (u64, u16)>,
+        /// Number of fee change records ever written
+        fee_history_count: u32,
+        /// Whether blocking an account voids its outstanding authorizations
+        cancel_on_freeze: bool,
+        /// Per-payer epoch signed into authorizations; bumped on freeze
+        authorization_epochs: Mapping<AccountId, u32>,
+        /// Payment nonce hashes already refunded through a signed refund
+        refunded_nonces: Mapping<[u8; 32], bool>,
+        /// Number of nonces each payer has consumed
+        used_nonce_counts: Mapping<AccountId, u32>,
+        /// Framing applied to signed messages before hashing
+        signing_format: SigningFormat,
+        /// Cap on total supply enforced by `mint`; None means uncapped
+        max_supply: Option<Balance>,
+        /// Longest accepted `valid_until - block_timestamp`; None means no limit
+        max_validity_window_ms: Option<u64>,
+        /// Granted `(account, role)` pairs
+        roles: Mapping<(AccountId, u8), bool>,
+        /// Recipients whose incoming authorized payments pay no facilitator fee
+        recipient_fee_exempt: Mapping<AccountId, bool>,
+        /// Payers whose outgoing authorized payments pay no facilitator fee
+        payer_fee_exempt: Mapping<AccountId, bool>,
+        /// Longest accepted delay between `issued_at` and settlement for v2
+        /// authorizations; None means no limit
+        max_submission_delay_ms: Option<u64>,
+        /// Authorized signers and threshold of multisig payers
+        multisig_configs: Mapping<AccountId, (Vec<AccountId>, u8)>,
+        /// Sum of net amounts moved by authorized settlements
+        total_volume_settled: Balance,
+        /// Sum of facilitator fees collected by authorized settlements
+        total_fees_collected: Balance,
+        /// Extra fee charged on settlements submitted within the late grace period
+        late_settlement_surcharge_bps: u16,
+        /// Grace period past `valid_until`, in milliseconds, during which
+        /// settlement is still accepted with a surcharge
+        allow_late_within_ms: u64,
+        /// Token contract facilitator fees are collected in; None skims the
+        /// fee from the payment itself
+        fee_token: Option<AccountId>,
+        /// Clock-skew allowance past `valid_until` before an authorization counts
+        /// as expired
+        expiry_grace_ms: u64,
+        /// Smallest amount an authorization may settle
+        min_amount: Balance,
+        /// Delay before a facilitator fee increase takes effect
+        fee_timelock_ms: u64,
+        /// Scheduled fee increase as `(fee_bps, effective_at)`
+        pending_fee: Option<(u16, u64)>,
+        /// Ring of recent settlements, by slot
+        payment_history: Mapping<u32, PaymentRecord>,
+        /// Number of settlements ever recorded in `payment_history`
+        payment_history_count: u32,
+        /// Message versions accepted by `transfer_with_authorization_versioned`
+        accepted_message_versions: Mapping<u8, bool>,
+        /// Rounding applied to fractional facilitator fees
+        fee_rounding: FeeRounding,
+        /// Whether balance changes are checkpointed for `balance_of_at`
+        checkpoints_enabled: bool,
+        /// Per-account balance checkpoints as `(block_number, balance)`, by index
+        balance_checkpoints: Mapping<(AccountId, u32), (BlockNumber, Balance)>,
+        /// Number of checkpoints recorded per account
+        checkpoint_counts: Mapping<AccountId, u32>,
+        /// Contract each recipient wants notified of settlements it receives
+        payment_hooks: Mapping<AccountId, AccountId>,
+        /// Smallest facilitator fee charged on a non-exempt settlement
+        min_fee: Balance,
+        /// Expiry timestamps of allowances granted with `approve_until`
+        allowance_expiries: Mapping<(AccountId, AccountId), u64>,
+        /// Highest `valid_until` among each payer's pruned nonces; authorizations
+        /// valid until then or earlier are rejected
+        pruned_through: Mapping<AccountId, u64>,
+        /// Nonce hashes of authorizations canceled by their payer
+        canceled_nonces: Mapping<[u8; 32], ()>,
+        /// Net amount each recipient received under a payment nonce hash, less
+        /// what it has refunded since
+        refundable: Mapping<([u8; 32], AccountId), Balance>,
+        /// Scheduled fee timelock reduction as `(timelock_ms, effective_at)`
+        pending_fee_timelock: Option<(u64, u64)>,
+        /// Scheduled minimum fee increase as `(min_fee, effective_at)`
+        pending_min_fee: Option<(Balance, u64)>,
+        /// `valid_until` of partially drawn authorizations, by nonce hash
+        partial_valid_until: Mapping<[u8; 32], u64>,
     }
 
     /// Events
@@ -83,6 +508,12 @@ mod httpusd {
         value: Balance,
     }
 
+    /// Event emitted on every signature verification, for debugging clients
+    ///
+    /// Malformed signatures are reported too, with `signature_valid: false`
+    /// and the submitted length. Only built with the `debug-events` feature;
+    /// the default build neither defines nor emits it.
+    #[cfg(feature = "debug-events")]
     #[ink(event)]
     pub struct DebugSignature {
         message_hash: [u8; 32],
@@ -96,27 +527,417 @@ mod httpusd {
         from: AccountId,
         #[ink(topic)]
         to: AccountId,
+        /// Replay-protection key of the nonce, so indexers can filter by authorization
+        #[ink(topic)]
+        nonce_hash: [u8; 32],
         amount: Balance,
         facilitator_fee: Balance,
         nonce: String,
     }
 
+    /// Emitted as soon as an authorization's nonce is marked used, before any
+    /// balance moves. Note that if the call subsequently fails, the runtime
+    /// reverts the whole call (nonce and event included); the event is then
+    /// only visible in dry-run results.
+    #[ink(event)]
+    pub struct NonceConsumed {
+        #[ink(topic)]
+        from: AccountId,
+        nonce_hash: [u8; 32],
+        block_number: BlockNumber,
+    }
+
+    /// Event emitted when a payer cancels an authorization before it is settled
+    #[ink(event)]
+    pub struct AuthorizationCanceled {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        nonce_hash: [u8; 32],
+        nonce: String,
+    }
+
+    #[ink(event)]
+    pub struct MetadataChanged {
+        name: Option<String>,
+        symbol: Option<String>,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferred {
+        #[ink(topic)]
+        previous_owner: AccountId,
+        #[ink(topic)]
+        new_owner: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Paused {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Unpaused {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct AuthorizationsPaused {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct AuthorizationsUnpaused {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    /// Event emitted when the contract code is replaced
+    #[ink(event)]
+    pub struct CodeUpgraded {
+        code_hash: [u8; 32],
+        #[ink(topic)]
+        upgraded_by: AccountId,
+    }
+
+    /// Event emitted when a role is granted
+    #[ink(event)]
+    pub struct RoleGranted {
+        role: u8,
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        granted_by: AccountId,
+    }
+
+    /// Event emitted when a role is revoked
+    #[ink(event)]
+    pub struct RoleRevoked {
+        role: u8,
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        revoked_by: AccountId,
+    }
+
+    /// Event emitted when the facilitator fee changes
+    #[ink(event)]
+    pub struct FeeChanged {
+        old_bps: u16,
+        new_bps: u16,
+        #[ink(topic)]
+        changed_by: AccountId,
+    }
+
+    /// Event emitted when an owner setter without a dedicated event changes
+    /// configuration; `setting` names the storage field
+    #[ink(event)]
+    pub struct ConfigChanged {
+        #[ink(topic)]
+        changed_by: AccountId,
+        setting: String,
+    }
+
+    /// Event emitted when an authorization is rejected for a compliance reason
+    ///
+    /// The rejection is returned as an error, which reverts the call, so this is
+    /// only observable in dry-runs and off-chain tests, not in finalized blocks,
+    /// except for items of `transfer_with_authorization_batch`.
+    #[ink(event)]
+    pub struct PaymentFailed {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        #[ink(topic)]
+        nonce_hash: [u8; 32],
+        nonce: String,
+        reason: String,
+    }
+
+    /// Summary emitted at the end of `transfer_with_authorization_batch`
+    ///
+    /// Counts saturate at `u16::MAX`. `total_fee` is the facilitator fee charged
+    /// on the settled items.
+    #[ink(event)]
+    pub struct BatchSettled {
+        count_success: u16,
+        count_failed: u16,
+        total_net: Balance,
+        total_fee: Balance,
+    }
+
+    /// Event emitted when an authorization settles within the late grace period
+    #[ink(event)]
+    pub struct LateSettlement {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        nonce: String,
+        late_by_ms: u64,
+        surcharge_bps: u16,
+    }
+
+    /// Event emitted when a recipient refunds a settled payment
+    #[ink(event)]
+    pub struct Refunded {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        amount: Balance,
+        original_nonce: String,
+    }
+
+    /// Event emitted when a settlement's skimmed fee could not be moved to the
+    /// fee recipient
+    ///
+    /// The payment to the recipient still succeeds; `shortfall` is the fee left
+    /// with the payer. Payers are checked to cover the full amount before
+    /// settling, so this only reports unexpected failures such as an overflow
+    /// of the fee recipient's balance.
+    #[ink(event)]
+    pub struct FeeTransferFailed {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        fee_recipient: AccountId,
+        #[ink(topic)]
+        nonce_hash: [u8; 32],
+        shortfall: Balance,
+    }
+
+    /// Event emitted when a recipient's payment hook fails
+    ///
+    /// The settlement itself still succeeds.
+    #[ink(event)]
+    pub struct PaymentHookFailed {
+        #[ink(topic)]
+        recipient: AccountId,
+        #[ink(topic)]
+        hook: AccountId,
+        nonce: String,
+    }
+
     impl Httpusd {
         /// Constructor
+        ///
+        /// Fails with `FeeTooHigh` if `facilitator_fee_bps` exceeds `DEFAULT_MAX_FEE_BPS`.
         #[ink(constructor)]
-        pub fn new(initial_supply: Balance, facilitator_fee_bps: u16) -> Self {
+        pub fn new(initial_supply: Balance, facilitator_fee_bps: u16) -> Result<Self> {
+            if facilitator_fee_bps > DEFAULT_MAX_FEE_BPS {
+                return Err(Error::FeeTooHigh);
+            }
             let caller = Self::env().caller();
             let mut balances = Mapping::default();
             balances.insert(caller, &initial_supply);
 
-            Self {
+            // Minting the initial supply is reported with no sender, per PSP22
+            Self::env().emit_event(Transfer {
+                from: None,
+                to: Some(caller),
+                value: initial_supply,
+            });
+
+            let mut contract = Self {
                 total_supply: initial_supply,
                 balances,
                 allowances: Mapping::default(),
                 used_nonces: Mapping::default(),
                 owner: caller,
+                pending_owner: None,
                 facilitator_fee_bps,
+                max_fee_bps: DEFAULT_MAX_FEE_BPS,
+                fee_recipient: caller,
+                token_name: None,
+                token_symbol: None,
+                decimals: DEFAULT_DECIMALS,
+                allow_no_expiry: false,
+                namespace_gating: false,
+                nonce_namespaces: Mapping::default(),
+                chain_id: 0,
+                domain_separator: Self::compute_domain_separator(0, Self::env().account_id()),
+                authorizations_paused: false,
+                paused: false,
+                unique_holders: if initial_supply > 0 { 1 } else { 0 },
+                last_settled_block: Mapping::default(),
+                owner_cannot_self_authorize: false,
+                spend_limit_per_day: None,
+                daily_spent: Mapping::default(),
+                idempotency_records: Mapping::default(),
+                idempotency_window_ms: DEFAULT_IDEMPOTENCY_WINDOW_MS,
+                partial_remaining: Mapping::default(),
+                blocklist: Mapping::default(),
+                fee_history: Mapping::default(),
+                fee_history_count: 0,
+                cancel_on_freeze: false,
+                authorization_epochs: Mapping::default(),
+                refunded_nonces: Mapping::default(),
+                used_nonce_counts: Mapping::default(),
+                signing_format: SigningFormat::Raw,
+                max_supply: None,
+                max_validity_window_ms: None,
+                roles: Mapping::default(),
+                recipient_fee_exempt: Mapping::default(),
+                payer_fee_exempt: Mapping::default(),
+                max_submission_delay_ms: None,
+                multisig_configs: Mapping::default(),
+                total_volume_settled: 0,
+                total_fees_collected: 0,
+                late_settlement_surcharge_bps: 0,
+                allow_late_within_ms: 0,
+                fee_token: None,
+                expiry_grace_ms: 0,
+                min_amount: 0,
+                fee_timelock_ms: 0,
+                pending_fee: None,
+                payment_history: Mapping::default(),
+                payment_history_count: 0,
+                accepted_message_versions: Mapping::default(),
+                fee_rounding: FeeRounding::Down,
+                checkpoints_enabled: false,
+                balance_checkpoints: Mapping::default(),
+                checkpoint_counts: Mapping::default(),
+                payment_hooks: Mapping::default(),
+                min_fee: 0,
+                allowance_expiries: Mapping::default(),
+                pruned_through: Mapping::default(),
+                canceled_nonces: Mapping::default(),
+                refundable: Mapping::default(),
+                pending_fee_timelock: None,
+                pending_min_fee: None,
+                partial_valid_until: Mapping::default(),
+            };
+            contract.accepted_message_versions.insert(MESSAGE_VERSION_1, &true);
+            contract.grant_all_roles(caller);
+            contract.record_fee_change();
+            Ok(contract)
+        }
+
+        /// Constructor with a custom facilitator fee cap
+        ///
+        /// `max_fee_bps` may not exceed 10000 (100%), and the initial fee may
+        /// not exceed `max_fee_bps`.
+        #[ink(constructor)]
+        pub fn new_with_max_fee(
+            initial_supply: Balance,
+            facilitator_fee_bps: u16,
+            max_fee_bps: u16,
+        ) -> Result<Self> {
+            if max_fee_bps > 10_000 || facilitator_fee_bps > max_fee_bps {
+                return Err(Error::FeeTooHigh);
+            }
+            let mut contract = Self::new(initial_supply, 0)?;
+            contract.facilitator_fee_bps = facilitator_fee_bps;
+            contract.max_fee_bps = max_fee_bps;
+            contract.record_fee_change();
+            Ok(contract)
+        }
+
+        /// Constructor with PSP22Metadata
+        #[ink(constructor)]
+        pub fn new_with_metadata(
+            initial_supply: Balance,
+            facilitator_fee_bps: u16,
+            name: Option<String>,
+            symbol: Option<String>,
+            decimals: u8,
+        ) -> Result<Self> {
+            let too_long =
+                |value: &Option<String>| value.as_ref().is_some_and(|v| v.len() > MAX_METADATA_LEN);
+            if too_long(&name) || too_long(&symbol) {
+                return Err(Error::MetadataTooLong);
+            }
+
+            let mut contract = Self::new(initial_supply, facilitator_fee_bps)?;
+            contract.token_name = name;
+            contract.token_symbol = symbol;
+            contract.decimals = decimals;
+            Ok(contract)
+        }
+
+        /// Constructor binding authorizations to a specific chain id
+        #[ink(constructor)]
+        pub fn new_with_chain_id(
+            initial_supply: Balance,
+            facilitator_fee_bps: u16,
+            chain_id: u32,
+        ) -> Result<Self> {
+            let mut contract = Self::new(initial_supply, facilitator_fee_bps)?;
+            contract.chain_id = chain_id;
+            contract.domain_separator =
+                Self::compute_domain_separator(chain_id, Self::env().account_id());
+            Ok(contract)
+        }
+
+        /// Constructor routing facilitator fees to a dedicated account
+        ///
+        /// Passing the zero account as `fee_recipient` defaults it to the caller.
+        #[ink(constructor)]
+        pub fn new_with_fee_recipient(
+            initial_supply: Balance,
+            facilitator_fee_bps: u16,
+            fee_recipient: AccountId,
+        ) -> Result<Self> {
+            let mut contract = Self::new(initial_supply, facilitator_fee_bps)?;
+            if fee_recipient != AccountId::from([0u8; 32]) {
+                contract.fee_recipient = fee_recipient;
             }
+            Ok(contract)
+        }
+
+        /// Constructor choosing how fractional facilitator fees are rounded
+        ///
+        /// `new` and the other constructors round down.
+        #[ink(constructor)]
+        pub fn new_with_fee_rounding(
+            initial_supply: Balance,
+            facilitator_fee_bps: u16,
+            fee_rounding: FeeRounding,
+        ) -> Result<Self> {
+            let mut contract = Self::new(initial_supply, facilitator_fee_bps)?;
+            contract.fee_rounding = fee_rounding;
+            Ok(contract)
+        }
+
+        /// Constructor recording balance checkpoints for `balance_of_at`
+        ///
+        /// Every balance change writes a checkpoint, at most one per account per
+        /// block, so storage grows by one `(BlockNumber, Balance)` entry for each
+        /// block in which an account's balance changes. Checkpointing can only be
+        /// chosen at construction so the history has no gaps.
+        #[ink(constructor)]
+        pub fn new_with_checkpoints(
+            initial_supply: Balance,
+            facilitator_fee_bps: u16,
+        ) -> Result<Self> {
+            let mut contract = Self::new(initial_supply, facilitator_fee_bps)?;
+            contract.checkpoints_enabled = true;
+            contract.write_checkpoint(Self::env().caller(), initial_supply);
+            Ok(contract)
+        }
+
+        /// Constructor with a cap on total supply
+        ///
+        /// `mint` can never take total supply above `max_supply`; None means
+        /// uncapped. Fails with `MaxSupplyExceeded` if `initial_supply` is
+        /// already above the cap.
+        #[ink(constructor)]
+        pub fn new_with_max_supply(
+            initial_supply: Balance,
+            facilitator_fee_bps: u16,
+            max_supply: Option<Balance>,
+        ) -> Result<Self> {
+            if max_supply.is_some_and(|cap| initial_supply > cap) {
+                return Err(Error::MaxSupplyExceeded);
+            }
+            let mut contract = Self::new(initial_supply, facilitator_fee_bps)?;
+            contract.max_supply = max_supply;
+            Ok(contract)
         }
 
         // ============================================================
@@ -129,10 +950,22 @@ mod httpusd {
             self.total_supply
         }
 
+        /// Returns the token name
+        #[ink(message)]
+        pub fn token_name(&self) -> Option<String> {
+            self.token_name.clone()
+        }
+
+        /// Returns the token symbol
+        #[ink(message)]
+        pub fn token_symbol(&self) -> Option<String> {
+            self.token_symbol.clone()
+        }
+
         /// Returns the number of decimals for the token
         #[ink(message)]
         pub fn decimals(&self) -> u8 {
-            12
+            self.decimals
         }
 
         /// Returns the balance of an account
@@ -141,30 +974,165 @@ mod httpusd {
             self.balances.get(owner).unwrap_or(0)
         }
 
+        /// Balances of several accounts at once
+        ///
+        /// Returns one balance per account, in input order, with 0 for accounts
+        /// holding nothing. Fails with `TooManyQueries` for more than
+        /// `MAX_BALANCE_QUERIES` accounts; callers should split the batch.
+        #[ink(message)]
+        pub fn balances_of(&self, accounts: Vec<AccountId>) -> Result<Vec<Balance>> {
+            if accounts.len() > MAX_BALANCE_QUERIES {
+                return Err(Error::TooManyQueries);
+            }
+            Ok(accounts.iter().map(|account| self.balance_of(*account)).collect())
+        }
+
+        /// Balance of `owner` as of the end of `block_number`
+        ///
+        /// Returns the latest checkpoint at or before `block_number`, or 0 if
+        /// there is none. Always 0 unless the contract was deployed with
+        /// `new_with_checkpoints`.
+        #[ink(message)]
+        pub fn balance_of_at(&self, owner: AccountId, block_number: BlockNumber) -> Balance {
+            let count = self.checkpoint_counts.get(owner).unwrap_or(0);
+            // Binary search for the first checkpoint after `block_number`
+            let (mut low, mut high) = (0u32, count);
+            while low < high {
+                let mid = low + (high - low) / 2;
+                let (block, _) = self.balance_checkpoints.get((owner, mid)).unwrap_or_default();
+                if block <= block_number {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+            if low == 0 {
+                return 0;
+            }
+            self.balance_checkpoints
+                .get((owner, low - 1))
+                .map(|(_, balance)| balance)
+                .unwrap_or(0)
+        }
+
+        /// Returns the number of accounts currently holding a nonzero balance
+        ///
+        /// Incremented when a balance goes from zero to nonzero and decremented
+        /// when it returns to zero.
+        #[ink(message)]
+        pub fn unique_holders(&self) -> u64 {
+            self.unique_holders
+        }
+
         /// Returns the allowance
+        ///
+        /// An allowance granted with `approve_until` reads as 0 once expired.
         #[ink(message)]
         pub fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+            let expired = self
+                .allowance_expiries
+                .get((owner, spender))
+                .is_some_and(|valid_until| self.env().block_timestamp() > valid_until);
+            if expired {
+                return 0;
+            }
             self.allowances.get((owner, spender)).unwrap_or(0)
         }
 
+        /// Expiry timestamp of the allowance, if granted with `approve_until`
+        #[ink(message)]
+        pub fn allowance_expiry(&self, owner: AccountId, spender: AccountId) -> Option<u64> {
+            self.allowance_expiries.get((owner, spender))
+        }
+
         /// Standard PSP22 transfer
+        ///
+        /// Fails fast with `AmountExceedsSupply` if `value` exceeds total supply.
         #[ink(message)]
         pub fn transfer(&mut self, to: AccountId, value: Balance) -> Result<()> {
             let from = self.env().caller();
+            Self::ensure_nonzero_parties(from, to)?;
+            self.ensure_within_supply(value)?;
             self.transfer_from_to(from, to, value)?;
             Ok(())
         }
 
+        /// Cap on total supply, if any
+        #[ink(message)]
+        pub fn max_supply(&self) -> Option<Balance> {
+            self.max_supply
+        }
+
         /// Approve spender to spend tokens
+        ///
+        /// The allowance does not expire, replacing any earlier `approve_until`.
         #[ink(message)]
         pub fn approve(&mut self, spender: AccountId, value: Balance) -> Result<()> {
             let owner = self.env().caller();
             self.allowances.insert((owner, spender), &value);
+            self.allowance_expiries.remove((owner, spender));
+            self.env().emit_event(Approval { owner, spender, value });
+            Ok(())
+        }
+
+        /// Approve spender to spend tokens until `valid_until`
+        ///
+        /// After `valid_until` the allowance reads as 0, so `transfer_from`
+        /// fails with `InsufficientAllowance`. `increase_allowance` and
+        /// `decrease_allowance` keep the expiry until it passes; `approve`
+        /// removes it.
+        #[ink(message)]
+        pub fn approve_until(
+            &mut self,
+            spender: AccountId,
+            value: Balance,
+            valid_until: u64,
+        ) -> Result<()> {
+            let owner = self.env().caller();
+            self.allowances.insert((owner, spender), &value);
+            self.allowance_expiries.insert((owner, spender), &valid_until);
+            self.env().emit_event(Approval { owner, spender, value });
+            Ok(())
+        }
+
+        /// Increase the allowance granted to `spender` by `delta`
+        ///
+        /// An expired `approve_until` allowance counts as 0 and loses its
+        /// expiry, so the increased allowance does not expire.
+        #[ink(message)]
+        pub fn increase_allowance(&mut self, spender: AccountId, delta: Balance) -> Result<()> {
+            let owner = self.env().caller();
+            self.clear_expired_allowance(owner, spender);
+            let value = self
+                .allowance(owner, spender)
+                .checked_add(delta)
+                .ok_or(Error::PSP22(PSP22Error::Overflow))?;
+            self.allowances.insert((owner, spender), &value);
+            self.env().emit_event(Approval { owner, spender, value });
+            Ok(())
+        }
+
+        /// Decrease the allowance granted to `spender` by `delta`
+        ///
+        /// Per PSP22, this does not saturate: it fails with `InsufficientAllowance`
+        /// if `delta` exceeds the current allowance. An expired `approve_until`
+        /// allowance counts as 0 and loses its expiry.
+        #[ink(message)]
+        pub fn decrease_allowance(&mut self, spender: AccountId, delta: Balance) -> Result<()> {
+            let owner = self.env().caller();
+            self.clear_expired_allowance(owner, spender);
+            let value = self
+                .allowance(owner, spender)
+                .checked_sub(delta)
+                .ok_or(Error::PSP22(PSP22Error::InsufficientAllowance))?;
+            self.allowances.insert((owner, spender), &value);
             self.env().emit_event(Approval { owner, spender, value });
             Ok(())
         }
 
         /// Transfer from another account (requires allowance)
+        ///
+        /// Fails fast with `AmountExceedsSupply` if `value` exceeds total supply.
         #[ink(message)]
         pub fn transfer_from(
             &mut self,
@@ -172,6 +1140,8 @@ mod httpusd {
             to: AccountId,
             value: Balance,
         ) -> Result<()> {
+            Self::ensure_nonzero_parties(from, to)?;
+            self.ensure_within_supply(value)?;
             let caller = self.env().caller();
             let allowance = self.allowance(from, caller);
 
@@ -186,234 +1156,8021 @@ mod httpusd {
             Ok(())
         }
 
-        // ============================================================
-        // X402 TRANSFER WITH AUTHORIZATION
-        // ============================================================
-
-        /// Execute a signed payment authorization (X402)
+        /// Set `owner`'s allowance for `spender` from a signed approval
         ///
-        /// # Arguments
-        /// * `from` - Account that signed the authorization
-        /// * `to` - Recipient account
-        /// * `amount` - Amount to transfer (before fees)
-        /// * `valid_until` - Timestamp when authorization expires
-        /// * `nonce` - Unique nonce string to prevent replay
-        /// * `signature` - sr25519 signature (64 bytes)
-        ///
-        /// # Returns
-        /// Result with () or Error
+        /// The owner signs `APPROVE_MESSAGE_PREFIX ++ payment_message` with
+        /// `spender` and `value` in place of `to` and `amount`, so approval and
+        /// transfer signatures never verify for each other. Approval nonces are
+        /// tracked apart from transfer nonces (see `is_approval_nonce_used`).
+        /// Approvals get no late settlement grace.
         #[ink(message)]
-        pub fn transfer_with_authorization(
+        #[allow(clippy::too_many_arguments)]
+        pub fn approve_with_authorization(
             &mut self,
-            from: AccountId,
-            to: AccountId,
-            amount: Balance,
+            owner: AccountId,
+            spender: AccountId,
+            value: Balance,
             valid_until: u64,
             nonce: String,
             signature: Vec<u8>,
+            scheme: SignatureScheme,
         ) -> Result<()> {
-            // 1. Check if payment has expired
-            let current_time = self.env().block_timestamp();
-            if current_time > valid_until {
+            self.ensure_authorizations_open(owner)?;
+            Self::ensure_nonzero_parties(owner, spender)?;
+            Self::validate_nonce(&nonce)?;
+            let nonce_hash = self.compute_nonce_hash_approval(&owner, &nonce);
+            if self.check_expiry(owner, valid_until)?.is_some() {
                 return Err(Error::PaymentExpired);
             }
+            self.ensure_nonce_available(nonce_hash)?;
 
-            // 2. Check if nonce has been used (prevent replay attacks)
-            let nonce_hash = self.compute_nonce_hash(&from, &nonce);
-            if self.used_nonces.get(nonce_hash).unwrap_or(false) {
-                return Err(Error::NonceAlreadyUsed);
+            if self.multisig_configs.contains(owner) {
+                return Err(Error::ThresholdNotMet);
             }
-
-            // 3. Verify signature
-            if !self.verify_signature(from, to, amount, &nonce, valid_until, &signature) {
+            let mut message = APPROVE_MESSAGE_PREFIX.to_vec();
+            let approval = self.payment_message(owner, spender, value, &nonce, valid_until);
+            message.extend_from_slice(&approval);
+            if !self.verify_signed_message(owner, &message, &signature, scheme) {
                 return Err(Error::InvalidSignature);
             }
 
-            // 4. Validate amount
-            if amount == 0 {
-                return Err(Error::PSP22(PSP22Error::InsufficientBalance));
-            }
-
-            // 5. Calculate facilitator fee
-            let facilitator_fee = amount
-                .checked_mul(self.facilitator_fee_bps as u128)
-                .and_then(|v| v.checked_div(10000))
-                .ok_or(Error::PSP22(PSP22Error::InsufficientBalance))?;
-
-            let net_amount = amount
-                .checked_sub(facilitator_fee)
-                .ok_or(Error::PSP22(PSP22Error::InsufficientBalance))?;
+            self.consume_nonce(owner, nonce_hash, valid_until);
+            self.allowances.insert((owner, spender), &value);
+            self.allowance_expiries.remove((owner, spender));
+            self.env().emit_event(Approval { owner, spender, value });
+            Ok(())
+        }
 
-            // 6. Mark nonce as used BEFORE transfer (prevent reentrancy)
-            self.used_nonces.insert(nonce_hash, &true);
+        /// Check if an `approve_with_authorization` nonce has been used
+        #[ink(message)]
+        pub fn is_approval_nonce_used(&self, owner: AccountId, nonce: String) -> bool {
+            self.used_nonces.contains(self.compute_nonce_hash_approval(&owner, &nonce))
+        }
 
-            // 7. Execute transfer from 'from' to 'to'
-            self.transfer_from_to(from, to, net_amount)?;
+        // ============================================================
+        // SUPPLY MANAGEMENT
+        // ============================================================
 
-            // 8. Transfer fee to facilitator (caller/owner)
-            if facilitator_fee > 0 {
-                let _ = self.transfer_from_to(from, self.owner, facilitator_fee);
+        /// Mint new tokens to `to` (only owner)
+        #[ink(message)]
+        pub fn mint(&mut self, to: AccountId, amount: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            let new_supply = self
+                .total_supply
+                .checked_add(amount)
+                .ok_or(Error::PSP22(PSP22Error::Overflow))?;
+            if self.max_supply.is_some_and(|cap| new_supply > cap) {
+                return Err(Error::MaxSupplyExceeded);
             }
+            let new_balance = self
+                .balance_of(to)
+                .checked_add(amount)
+                .ok_or(Error::PSP22(PSP22Error::Overflow))?;
 
-            // 9. Emit event
-            self.env().emit_event(TransferWithAuthorization {
-                from,
-                to,
-                amount: net_amount,
-                facilitator_fee,
-                nonce,
+            self.total_supply = new_supply;
+            self.set_balance(to, new_balance);
+            self.env().emit_event(Transfer {
+                from: None,
+                to: Some(to),
+                value: amount,
             });
-
             Ok(())
         }
 
-        /// Check if a nonce has been used
+        /// Burn tokens held by `from` (only owner)
         #[ink(message)]
-        pub fn is_nonce_used(&self, from: AccountId, nonce: String) -> bool {
-            let nonce_hash = self.compute_nonce_hash(&from, &nonce);
-            self.used_nonces.get(nonce_hash).unwrap_or(false)
+        pub fn burn(&mut self, from: AccountId, amount: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            let from_balance = self.balance_of(from);
+            if from_balance < amount {
+                return Err(Error::PSP22(PSP22Error::InsufficientBalance));
+            }
+            let new_supply = self
+                .total_supply
+                .checked_sub(amount)
+                .ok_or(Error::PSP22(PSP22Error::InsufficientBalance))?;
+            let new_balance = from_balance
+                .checked_sub(amount)
+                .ok_or(Error::PSP22(PSP22Error::InsufficientBalance))?;
+
+            self.total_supply = new_supply;
+            self.set_balance(from, new_balance);
+            self.env().emit_event(Transfer {
+                from: Some(from),
+                to: None,
+                value: amount,
+            });
+            Ok(())
         }
 
         // ============================================================
-        // ADMIN FUNCTIONS
+        // X402 TRANSFER WITH AUTHORIZATION
         // ============================================================
 
-        /// Get the facilitator fee in basis points
+        /// Execute a signed payment authorization (X402)
+        ///
+        /// # Arguments
+        /// * `from` - Account that signed the authorization
+        /// * `to` - Recipient account
+        /// * `amount` - Amount to transfer (before fees)
+        /// * `valid_until` - Timestamp when authorization expires
+        /// * `nonce` - Unique nonce string to prevent replay
+        /// * `signature` - sr25519 (64 bytes) or ECDSA (65 bytes) signature
+        /// * `scheme` - Scheme the signature was produced with
+        ///
+        /// # Returns
+        /// Result with () or Error
         #[ink(message)]
-        pub fn get_facilitator_fee(&self) -> u16 {
-            self.facilitator_fee_bps
+        #[allow(clippy::too_many_arguments)]
+        pub fn transfer_with_authorization(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            valid_until: u64,
+            nonce: String,
+            signature: Vec<u8>,
+            scheme: SignatureScheme,
+        ) -> Result<()> {
+            self.execute_authorization(
+                from,
+                to,
+                amount,
+                valid_until,
+                nonce,
+                signature,
+                scheme,
+                None,
+            )
         }
 
-        /// Update facilitator fee (only owner)
+        /// `transfer_with_authorization` with a fixed-size bytes32 nonce
+        ///
+        /// The signed message is `B32_NONCE_TAG ++ payment_message` with the raw
+        /// 32 nonce bytes in place of the string. Bytes32 nonces are tracked
+        /// separately from String nonces (see `is_nonce_used_b32`), and events
+        /// report them as 0x-prefixed hex.
         #[ink(message)]
-        pub fn set_facilitator_fee(&mut self, fee_bps: u16) -> Result<()> {
-            if self.env().caller() != self.owner {
-                return Err(Error::PSP22(PSP22Error::Custom(String::from("Not owner"))));
+        #[allow(clippy::too_many_arguments)]
+        pub fn transfer_with_authorization_b32(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            valid_until: u64,
+            nonce: [u8; 32],
+            signature: Vec<u8>,
+            scheme: SignatureScheme,
+        ) -> Result<()> {
+            let nonce_label = Self::hex_encode(&nonce);
+            self.ensure_authorizations_open(from)?;
+            Self::ensure_nonzero_parties(from, to)?;
+            let nonce_hash = self.compute_nonce_hash_b32(&from, &nonce);
+            self.ensure_not_blocked(from, to, &nonce_label, nonce_hash)?;
+            let late_by_ms = self.check_expiry(from, valid_until)?;
+            self.ensure_nonce_available(nonce_hash)?;
+
+            if self.multisig_configs.contains(from) {
+                return Err(Error::ThresholdNotMet);
             }
-            self.facilitator_fee_bps = fee_bps;
-            Ok(())
+            let mut message = B32_NONCE_TAG.to_vec();
+            let payment = self.payment_message_bytes(from, to, amount, &nonce, valid_until);
+            message.extend_from_slice(&payment);
+            if !self.verify_signed_message(from, &message, &signature, scheme) {
+                return Err(Error::InvalidSignature);
+            }
+
+            let settlement = self.prepare_settlement(from, to, amount, late_by_ms)?;
+            self.consume_nonce(from, nonce_hash, valid_until);
+            self.settle(from, to, settlement, nonce_label, nonce_hash)
         }
 
-        // ============================================================
-        // PRIVATE HELPER FUNCTIONS
-        // ============================================================
+        /// Check if a bytes32 nonce has been used
+        #[ink(message)]
+        pub fn is_nonce_used_b32(&self, from: AccountId, nonce: [u8; 32]) -> bool {
+            self.used_nonces.contains(self.compute_nonce_hash_b32(&from, &nonce))
+        }
 
-        /// Internal transfer helper
-        fn transfer_from_to(&mut self, from: AccountId, to: AccountId, value: Balance) -> Result<()> {
-            let from_balance = self.balance_of(from);
-            if from_balance < value {
-                return Err(Error::PSP22(PSP22Error::InsufficientBalance));
+        /// `transfer_with_authorization` with replay protection scoped to the recipient
+        ///
+        /// The nonce is tracked per `(from, to, nonce)` instead of per
+        /// `(from, nonce)`, so a payer may reuse a nonce for different
+        /// recipients; only a repeat to the same recipient is a replay. This is
+        /// weaker than the default and must be chosen deliberately by the
+        /// signer: the signed message is `SCOPED_NONCE_TAG ++ payment_message`,
+        /// so scoped and regular signatures never verify for each other.
+        #[ink(message)]
+        #[allow(clippy::too_many_arguments)]
+        pub fn transfer_with_authorization_scoped(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            valid_until: u64,
+            nonce: String,
+            signature: Vec<u8>,
+            scheme: SignatureScheme,
+        ) -> Result<()> {
+            self.ensure_authorizations_open(from)?;
+            Self::ensure_nonzero_parties(from, to)?;
+            Self::validate_nonce(&nonce)?;
+            let nonce_hash = self.compute_nonce_hash_scoped(&from, &to, &nonce);
+            self.ensure_not_blocked(from, to, &nonce, nonce_hash)?;
+            let late_by_ms = self.check_expiry(from, valid_until)?;
+            self.ensure_nonce_available(nonce_hash)?;
+
+            if self.multisig_configs.contains(from) {
+                return Err(Error::ThresholdNotMet);
+            }
+            let mut message = SCOPED_NONCE_TAG.to_vec();
+            message.extend_from_slice(&self.payment_message(from, to, amount, &nonce, valid_until));
+            if !self.verify_signed_message(from, &message, &signature, scheme) {
+                return Err(Error::InvalidSignature);
             }
 
-            let new_from_balance = from_balance.checked_sub(value)
-                .ok_or(Error::PSP22(PSP22Error::InsufficientBalance))?;
-            self.balances.insert(from, &new_from_balance);
+            let settlement = self.prepare_settlement(from, to, amount, late_by_ms)?;
+            self.consume_nonce(from, nonce_hash, valid_until);
+            self.settle(from, to, settlement, nonce, nonce_hash)
+        }
 
-            let to_balance = self.balance_of(to);
-            let new_to_balance = to_balance.checked_add(value)
-                .ok_or(Error::PSP22(PSP22Error::Custom(String::from("Overflow"))))?;
-            self.balances.insert(to, &new_to_balance);
+        /// Check if a recipient-scoped nonce has been used
+        #[ink(message)]
+        pub fn is_nonce_used_scoped(&self, from: AccountId, to: AccountId, nonce: String) -> bool {
+            self.used_nonces.contains(self.compute_nonce_hash_scoped(&from, &to, &nonce))
+        }
 
-            self.env().emit_event(Transfer {
-                from: Some(from),
-                to: Some(to),
-                value,
-            });
+        /// Settle an authorization from a multisig payer
+        ///
+        /// `from` must have registered a signer set with `set_multisig`. Each
+        /// `signatures[i]` is an sr25519 signature by `signers[i]` over the
+        /// regular payment message. Signers outside the registered set and
+        /// repeated signers are not counted; at least the registered threshold
+        /// must verify.
+        #[ink(message)]
+        #[allow(clippy::too_many_arguments)]
+        pub fn transfer_with_authorization_multisig(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            valid_until: u64,
+            nonce: String,
+            signers: Vec<AccountId>,
+            signatures: Vec<Vec<u8>>,
+        ) -> Result<()> {
+            let (authorized, threshold) =
+                self.multisig_configs.get(from).ok_or(Error::NotMultisig)?;
+            if signers.len() != signatures.len() {
+                return Err(Error::InvalidSignature);
+            }
+            let (nonce_hash, late_by_ms) =
+                self.authorization_preconditions(from, to, valid_until, &nonce)?;
 
-            Ok(())
+            let message = self.payment_message(from, to, amount, &nonce, valid_until);
+            let scheme = SignatureScheme::Sr25519;
+            let mut counted: Vec<AccountId> = Vec::new();
+            for (signer, signature) in signers.iter().zip(signatures.iter()) {
+                if !authorized.contains(signer) || counted.contains(signer) {
+                    continue;
+                }
+                if self.verify_signed_message(*signer, &message, signature, scheme) {
+                    counted.push(*signer);
+                }
+            }
+            if counted.len() < threshold as usize {
+                return Err(Error::ThresholdNotMet);
+            }
+
+            let settlement = self.prepare_settlement(from, to, amount, late_by_ms)?;
+            self.consume_nonce(from, nonce_hash, valid_until);
+            self.settle(from, to, settlement, nonce, nonce_hash)
         }
 
-        /// Compute a unique hash for the nonce
-        fn compute_nonce_hash(&self, from: &AccountId, nonce: &String) -> [u8; 32] {
-            let mut data = Vec::new();
-            data.extend_from_slice(from.as_ref());
-            data.extend_from_slice(nonce.as_bytes());
+        /// Registered multisig signers and threshold of `account`, if any
+        #[ink(message)]
+        pub fn get_multisig(&self, account: AccountId) -> Option<(Vec<AccountId>, u8)> {
+            self.multisig_configs.get(account)
+        }
 
-            let mut output = [0u8; 32];
-            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&data, &mut output);
-            output
+        /// Register the caller as a multisig payer
+        ///
+        /// Authorizations from the caller can then only be settled through
+        /// `transfer_with_authorization_multisig` with `threshold` of `signers`.
+        /// An empty `signers` removes the configuration.
+        #[ink(message)]
+        pub fn set_multisig(&mut self, signers: Vec<AccountId>, threshold: u8) -> Result<()> {
+            let caller = self.env().caller();
+            if signers.is_empty() {
+                self.multisig_configs.remove(caller);
+                return Ok(());
+            }
+            let has_duplicates =
+                signers.iter().enumerate().any(|(i, signer)| signers[..i].contains(signer));
+            if signers.len() > MAX_MULTISIG_SIGNERS
+                || has_duplicates
+                || threshold == 0
+                || threshold as usize > signers.len()
+            {
+                return Err(Error::InvalidMultisigConfig);
+            }
+            self.multisig_configs.insert(caller, &(signers, threshold));
+            Ok(())
         }
 
-        /// Verify sr25519 signature for the payment
-        fn verify_signature(
+        /// Dry-run `transfer_with_authorization` without changing any state
+        ///
+        /// Runs the same pause, party, expiry, nonce, signature and amount checks
+        /// and returns the exact error settlement would hit, so facilitators can
+        /// pre-flight before paying for a transaction. Balance sufficiency is not
+        /// checked, since balances can change before submission.
+        #[ink(message)]
+        #[allow(clippy::too_many_arguments)]
+        pub fn validate_authorization(
             &self,
             from: AccountId,
             to: AccountId,
             amount: Balance,
-            nonce: &String,
             valid_until: u64,
-            signature: &[u8],
-        ) -> bool {
-            // Build the message that was signed
-            use scale::Encode;
-            let mut message = Vec::new();
-            message.extend_from_slice(&from.encode());
-            message.extend_from_slice(&to.encode());
-            message.extend_from_slice(&amount.encode());
-            message.extend_from_slice(nonce.as_bytes());
-            message.extend_from_slice(&valid_until.encode());
+            nonce: String,
+            signature: Vec<u8>,
+            scheme: SignatureScheme,
+        ) -> Result<()> {
+            self.preflight_authorization(
+                from,
+                to,
+                amount,
+                valid_until,
+                &nonce,
+                &signature,
+                scheme,
+                None,
+            )
+            .map(|_| ())
+        }
 
-            // Hash the message using Blake2x256
-            let mut hash = [0u8; 32];
-            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&message, &mut hash);
+        /// Whether `signature` is a valid authorization for these parameters
+        ///
+        /// Checks only the signature over `payment_message`, not nonce, expiry,
+        /// balance or any other settlement condition, and never emits
+        /// `DebugSignature`.
+        #[ink(message)]
+        #[allow(clippy::too_many_arguments)]
+        pub fn verify_authorization_signature(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            nonce: String,
+            valid_until: u64,
+            signature: Vec<u8>,
+            scheme: SignatureScheme,
+        ) -> bool {
+            let message = self.payment_message(from, to, amount, &nonce, valid_until);
+            self.is_signature_valid(from, &message, &signature, scheme)
+        }
 
-            // Verify the sr25519 signature
-            let sig_len = signature.len();
-            if sig_len != 64 {
-                #[allow(clippy::cast_possible_truncation)]
-                self.env().emit_event(DebugSignature {
-                    message_hash: hash,
-                    signature_valid: false,
-                    signature_len: sig_len as u32,
-                });
-                return false;
+        /// `transfer_with_authorization` with a signed issuance time
+        ///
+        /// The signature covers `payment_message_v2`, which adds `issued_at`.
+        /// When `max_submission_delay_ms` is set, settlement is rejected with
+        /// `SubmissionTooLate` once more than that has passed since `issued_at`,
+        /// regardless of `valid_until`. An `issued_at` in the future counts as
+        /// no delay; it only weakens the signer's own protection.
+        #[ink(message)]
+        #[allow(clippy::too_many_arguments)]
+        pub fn transfer_with_authorization_v2(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            valid_until: u64,
+            issued_at: u64,
+            nonce: String,
+            signature: Vec<u8>,
+            scheme: SignatureScheme,
+        ) -> Result<()> {
+            if let Some(max_delay) = self.max_submission_delay_ms {
+                if self.env().block_timestamp().saturating_sub(issued_at) > max_delay {
+                    return Err(Error::SubmissionTooLate);
+                }
             }
+            self.execute_authorization(
+                from,
+                to,
+                amount,
+                valid_until,
+                nonce,
+                signature,
+                scheme,
+                Some(issued_at),
+            )
+        }
 
-            // Convert signature slice to fixed array
-            let mut sig_array = [0u8; 64];
-            sig_array.copy_from_slice(signature);
+        /// `transfer_with_authorization` with a declared message version
+        ///
+        /// The signed preimage is `version ++ payment_message`, so clients state
+        /// which layout they signed and the owner can accept several versions
+        /// during a migration (see `set_message_version_accepted`). Versions
+        /// outside the accepted set fail with `UnsupportedMessageVersion`.
+        /// Nonces are shared with `transfer_with_authorization`.
+        #[ink(message)]
+        #[allow(clippy::too_many_arguments)]
+        pub fn transfer_with_authorization_versioned(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            valid_until: u64,
+            nonce: String,
+            signature: Vec<u8>,
+            scheme: SignatureScheme,
+            version: u8,
+        ) -> Result<()> {
+            if !self.is_message_version_accepted(version) {
+                return Err(Error::UnsupportedMessageVersion);
+            }
+            let (nonce_hash, late_by_ms) =
+                self.authorization_preconditions(from, to, valid_until, &nonce)?;
 
-            // Convert AccountId to public key bytes
-            let pub_key: &[u8; 32] = from.as_ref();
+            if self.multisig_configs.contains(from) {
+                return Err(Error::ThresholdNotMet);
+            }
+            let mut message = Vec::from([version]);
+            message.extend_from_slice(&self.payment_message(from, to, amount, &nonce, valid_until));
+            if !self.verify_signed_message(from, &message, &signature, scheme) {
+                return Err(Error::InvalidSignature);
+            }
 
-            let is_valid = ink::env::sr25519_verify(&sig_array, &hash, pub_key).is_ok();
+            let settlement = self.prepare_settlement(from, to, amount, late_by_ms)?;
+            self.consume_nonce(from, nonce_hash, valid_until);
+            self.settle(from, to, settlement, nonce, nonce_hash)
+        }
 
-            #[allow(clippy::cast_possible_truncation)]
-            self.env().emit_event(DebugSignature {
-                message_hash: hash,
-                signature_valid: is_valid,
-                signature_len: sig_len as u32,
-            });
+        /// Whether `transfer_with_authorization_versioned` accepts `version`
+        #[ink(message)]
+        pub fn is_message_version_accepted(&self, version: u8) -> bool {
+            self.accepted_message_versions.contains(version)
+        }
 
-            is_valid
+        /// Add or remove a signed message version from the accepted set (only owner)
+        #[ink(message)]
+        pub fn set_message_version_accepted(&mut self, version: u8, accepted: bool) -> Result<()> {
+            self.ensure_owner()?;
+            if accepted {
+                self.accepted_message_versions.insert(version, &true);
+            } else {
+                self.accepted_message_versions.remove(version);
+            }
+            self.emit_config_changed("accepted_message_versions");
+            Ok(())
         }
-    }
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
+        /// Settle one authorization split among several recipients
+        ///
+        /// The payer signs `SPLIT_MESSAGE_PREFIX ++ payment_message` with the
+        /// Blake2x256 hash of the SCALE-encoded `recipients` in place of `to`,
+        /// so the shares cannot be rearranged. Shares must be nonzero, go to
+        /// distinct nonzero recipients (at most `MAX_SPLIT_RECIPIENTS`) and add
+        /// up to `amount`. One fee is charged on `amount`, as for a single
+        /// payment, and spread over the shares in proportion; the recipient
+        /// fee exemption applies only if every recipient is exempt. Each share
+        /// emits its own `TransferWithAuthorization`; the nonce is consumed
+        /// once for the whole split.
+        #[ink(message)]
+        #[allow(clippy::too_many_arguments)]
+        pub fn transfer_with_authorization_split(
+            &mut self,
+            from: AccountId,
+            amount: Balance,
+            valid_until: u64,
+            nonce: String,
+            signature: Vec<u8>,
+            scheme: SignatureScheme,
+            recipients: Vec<(AccountId, Balance)>,
+        ) -> Result<()> {
+            let Some(&(first, _)) = recipients.first() else {
+                return Err(Error::InvalidSplit);
+            };
+            if recipients.len() > MAX_SPLIT_RECIPIENTS {
+                return Err(Error::InvalidSplit);
+            }
+            let mut total: Balance = 0;
+            for (index, (to, share)) in recipients.iter().enumerate() {
+                if *share == 0 || recipients[..index].iter().any(|(seen, _)| seen == to) {
+                    return Err(Error::InvalidSplit);
+                }
+                total = total.checked_add(*share).ok_or(Error::SplitAmountMismatch)?;
+            }
+            if total != amount {
+                return Err(Error::SplitAmountMismatch);
+            }
 
-        #[ink::test]
-        fn new_works() {
-            let initial_supply = 1_000_000_000_000; // 1 trillion
-            let contract = Httpusd::new(initial_supply, 100); // 1% fee
-            assert_eq!(contract.total_supply(), initial_supply);
-            assert_eq!(contract.get_facilitator_fee(), 100);
-        }
+            let (nonce_hash, late_by_ms) =
+                self.authorization_preconditions(from, first, valid_until, &nonce)?;
+            for (to, _) in &recipients[1..] {
+                Self::ensure_nonzero_parties(from, *to)?;
+                self.ensure_not_blocked(from, *to, &nonce, nonce_hash)?;
+            }
 
-        #[ink::test]
+            if self.multisig_configs.contains(from) {
+                return Err(Error::ThresholdNotMet);
+            }
+            let recipients_hash = Self::hash_recipients(&recipients);
+            let mut message = SPLIT_MESSAGE_PREFIX.to_vec();
+            let payment = self.payment_message(from, recipients_hash, amount, &nonce, valid_until);
+            message.extend_from_slice(&payment);
+            if !self.verify_signed_message(from, &message, &signature, scheme) {
+                return Err(Error::InvalidSignature);
+            }
+            let fee_payee = recipients
+                .iter()
+                .map(|(to, _)| *to)
+                .find(|to| !self.is_fee_exempt(*to))
+                .unwrap_or(first);
+            let total = self.prepare_settlement(from, fee_payee, amount, late_by_ms)?;
+
+            self.consume_nonce(from, nonce_hash, valid_until);
+            // Each share carries the fee accrued on the shares so far, less what
+            // earlier shares carried, so the parts add up to exactly one fee
+            let mut settled: Balance = 0;
+            let mut fee_charged: Balance = 0;
+            for (to, share) in recipients {
+                settled += share;
+                let fee_through = total
+                    .facilitator_fee
+                    .checked_mul(settled)
+                    .ok_or(Error::PSP22(PSP22Error::Overflow))?
+                    / amount;
+                let facilitator_fee = fee_through - fee_charged;
+                fee_charged = fee_through;
+                let net_amount =
+                    if self.fee_token.is_some() { share } else { share - facilitator_fee };
+                let settlement = Settlement { facilitator_fee, net_amount, ..total };
+                self.settle(from, to, settlement, nonce.clone(), nonce_hash)?;
+            }
+            Ok(())
+        }
+
+        /// Draw part of a signed authorization for up to `max_amount`
+        ///
+        /// The payer signs `PARTIAL_MESSAGE_PREFIX ++ payment_message` with
+        /// `max_amount` as the amount, so a full authorization can never be
+        /// drawn piecemeal and vice versa. Each call transfers `draw_amount` (minus the fee) and
+        /// decrements the remaining capacity stored under the nonce hash; once it
+        /// reaches zero the nonce is marked used. A partially drawn nonce can no
+        /// longer be settled in full through `transfer_with_authorization`.
+        #[ink(message)]
+        #[allow(clippy::too_many_arguments)]
+        pub fn transfer_with_authorization_partial(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            max_amount: Balance,
+            draw_amount: Balance,
+            valid_until: u64,
+            nonce: String,
+            signature: Vec<u8>,
+            scheme: SignatureScheme,
+        ) -> Result<()> {
+            self.ensure_authorizations_open(from)?;
+            Self::ensure_nonzero_parties(from, to)?;
+            Self::validate_nonce(&nonce)?;
+            let nonce_hash = self.compute_nonce_hash(&from, &nonce);
+            self.ensure_not_blocked(from, to, &nonce, nonce_hash)?;
+            let late_by_ms = self.check_expiry(from, valid_until)?;
+            self.ensure_nonce_available(nonce_hash)?;
+
+            if self.multisig_configs.contains(from) {
+                return Err(Error::ThresholdNotMet);
+            }
+            let mut message = PARTIAL_MESSAGE_PREFIX.to_vec();
+            let payment = self.payment_message(from, to, max_amount, &nonce, valid_until);
+            message.extend_from_slice(&payment);
+            if !self.verify_signed_message(from, &message, &signature, scheme) {
+                return Err(Error::InvalidSignature);
+            }
+
+            let remaining = self.partial_remaining.get(nonce_hash).unwrap_or(max_amount);
+            if draw_amount > remaining {
+                return Err(Error::DrawExceedsRemaining);
+            }
+            let settlement = self.prepare_settlement(from, to, draw_amount, late_by_ms)?;
+
+            let new_remaining = remaining - draw_amount;
+            if new_remaining == 0 {
+                self.partial_remaining.remove(nonce_hash);
+                self.partial_valid_until.remove(nonce_hash);
+                self.consume_nonce(from, nonce_hash, valid_until);
+            } else {
+                self.partial_remaining.insert(nonce_hash, &new_remaining);
+                self.partial_valid_until.insert(nonce_hash, &valid_until);
+            }
+
+            self.settle(from, to, settlement, nonce, nonce_hash)
+        }
+
+        /// Remaining drawable amount of a partially drawn authorization
+        ///
+        /// Returns None if the authorization has not been partially drawn.
+        #[ink(message)]
+        pub fn partial_remaining(&self, from: AccountId, nonce: String) -> Option<Balance> {
+            let nonce_hash = self.compute_nonce_hash(&from, &nonce);
+            self.partial_remaining.get(nonce_hash)
+        }
+
+        /// `transfer_with_authorization` with relay-level idempotency
+        ///
+        /// `idempotency_key` is chosen by the relayer (scoped to the caller) and is
+        /// independent of the replay nonce. When the same key is resubmitted
+        /// within `idempotency_window_ms` for the same authorization, the
+        /// recorded successful outcome is returned without settling again; a
+        /// different authorization under the same key fails with
+        /// `IdempotencyKeyConflict`. Failed settlements revert and are therefore
+        /// never recorded, so a retry of a failed call is simply re-executed.
+        #[ink(message)]
+        #[allow(clippy::too_many_arguments)]
+        pub fn transfer_with_authorization_idempotent(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            valid_until: u64,
+            nonce: String,
+            signature: Vec<u8>,
+            scheme: SignatureScheme,
+            idempotency_key: String,
+        ) -> Result<()> {
+            Self::validate_nonce(&nonce)?;
+            let key_hash = self.idempotency_key_hash(&idempotency_key);
+            let nonce_hash = self.compute_nonce_hash(&from, &nonce);
+            let now = self.env().block_timestamp();
+
+            if let Some((recorded_hash, settled_at)) = self.idempotency_records.get(key_hash) {
+                if now.saturating_sub(settled_at) <= self.idempotency_window_ms {
+                    if recorded_hash == nonce_hash {
+                        return Ok(());
+                    }
+                    return Err(Error::IdempotencyKeyConflict);
+                }
+            }
+
+            self.transfer_with_authorization(
+                from,
+                to,
+                amount,
+                valid_until,
+                nonce,
+                signature,
+                scheme,
+            )?;
+            self.idempotency_records.insert(key_hash, &(nonce_hash, now));
+            Ok(())
+        }
+
+        /// Return `amount` of a settled payment from its recipient to its payer
+        ///
+        /// Only the original recipient `to` can refund, since it is the account
+        /// debited. The payment's nonce must have been consumed (fully or by a
+        /// partial draw) so refunds cannot reference payments that never happened,
+        /// and refunds across calls are capped at the net amount `to` received
+        /// under it (see `refundable_amount`).
+        #[ink(message)]
+        pub fn refund(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            original_nonce: String,
+        ) -> Result<()> {
+            self.when_not_paused()?;
+            if self.env().caller() != to {
+                return Err(Error::CallerNotRecipient);
+            }
+            let nonce_hash = self.compute_nonce_hash(&from, &original_nonce);
+            let remaining = self.remaining_refundable(nonce_hash, to, amount)?;
+
+            self.transfer_from_to(to, from, amount)?;
+            self.refundable.insert((nonce_hash, to), &remaining);
+
+            self.env().emit_event(Refunded {
+                from,
+                to,
+                amount,
+                original_nonce,
+            });
+            Ok(())
+        }
+
+        /// Refund a settled payment on the strength of a merchant signature
+        ///
+        /// `merchant` signs `refund_message` committing to the payer `to`, the
+        /// `amount` and the payer's `original_nonce`, so any relayer can submit
+        /// the refund. The original payment must have been settled to
+        /// `merchant`, the amount is capped like in `refund`, and each payment
+        /// can be refunded this way only once.
+        #[ink(message)]
+        pub fn refund_with_authorization(
+            &mut self,
+            merchant: AccountId,
+            to: AccountId,
+            amount: Balance,
+            original_nonce: String,
+            signature: Vec<u8>,
+            scheme: SignatureScheme,
+        ) -> Result<()> {
+            self.when_not_paused()?;
+            Self::ensure_nonzero_parties(merchant, to)?;
+            Self::validate_nonce(&original_nonce)?;
+
+            let nonce_hash = self.compute_nonce_hash(&to, &original_nonce);
+            let remaining = self.remaining_refundable(nonce_hash, merchant, amount)?;
+            if self.refunded_nonces.contains(nonce_hash) {
+                return Err(Error::NonceAlreadyUsed);
+            }
+
+            let message = self.refund_message(merchant, to, amount, &original_nonce);
+            if !self.verify_signed_message(merchant, &message, &signature, scheme) {
+                return Err(Error::InvalidSignature);
+            }
+
+            self.transfer_from_to(merchant, to, amount)?;
+            self.refunded_nonces.insert(nonce_hash, &true);
+            self.refundable.insert((nonce_hash, merchant), &remaining);
+
+            self.env().emit_event(Refunded {
+                from: to,
+                to: merchant,
+                amount,
+                original_nonce,
+            });
+            Ok(())
+        }
+
+        /// Amount `to` can still refund of the payment `from` made under `nonce`
+        ///
+        /// The net amount `to` received under the nonce (summed over partial
+        /// draws) minus refunds already made; 0 if `to` was not paid under it.
+        #[ink(message)]
+        pub fn refundable_amount(&self, from: AccountId, to: AccountId, nonce: String) -> Balance {
+            let nonce_hash = self.compute_nonce_hash(&from, &nonce);
+            self.refundable.get((nonce_hash, to)).unwrap_or(0)
+        }
+
+        /// Check if a nonce has been used
+        #[ink(message)]
+        pub fn is_nonce_used(&self, from: AccountId, nonce: String) -> bool {
+            let nonce_hash = self.compute_nonce_hash(&from, &nonce);
+            self.used_nonces.contains(nonce_hash)
+        }
+
+        /// Check several nonces at once
+        ///
+        /// Returns one flag per query, in input order. Fails with
+        /// `TooManyQueries` for more than `MAX_NONCE_QUERIES` queries; callers
+        /// should split the batch.
+        #[ink(message)]
+        pub fn are_nonces_used(&self, queries: Vec<(AccountId, String)>) -> Result<Vec<bool>> {
+            if queries.len() > MAX_NONCE_QUERIES {
+                return Err(Error::TooManyQueries);
+            }
+            Ok(queries
+                .iter()
+                .map(|(from, nonce)| {
+                    self.used_nonces.contains(self.compute_nonce_hash(from, nonce))
+                })
+                .collect())
+        }
+
+        /// Sum of net amounts moved by authorized settlements
+        ///
+        /// There is deliberately no way to reset this or `get_total_fees`.
+        #[ink(message)]
+        pub fn get_total_volume(&self) -> Balance {
+            self.total_volume_settled
+        }
+
+        /// Sum of facilitator fees collected by authorized settlements
+        #[ink(message)]
+        pub fn get_total_fees(&self) -> Balance {
+            self.total_fees_collected
+        }
+
+        /// Number of authorizations `account` has consumed
+        ///
+        /// Pruning expired nonces does not decrease the count.
+        #[ink(message)]
+        pub fn used_nonce_count(&self, account: AccountId) -> u32 {
+            self.used_nonce_counts.get(account).unwrap_or(0)
+        }
+
+        /// Canonical replay-protection key for `(from, nonce)`
+        ///
+        /// Blake2x256 over the 32 raw bytes of `from` followed by the UTF-8 bytes
+        /// of `nonce`. Clients and indexers should use this rather than hashing
+        /// themselves; the algorithm is fixed, as changing it would orphan every
+        /// nonce already recorded.
+        #[ink(message)]
+        pub fn nonce_hash(&self, from: AccountId, nonce: String) -> [u8; 32] {
+            self.compute_nonce_hash(&from, &nonce)
+        }
+
+        /// `nonce_hash` and `is_nonce_used` in one call, for debugging replay issues
+        #[ink(message)]
+        pub fn nonce_status(&self, from: AccountId, nonce: String) -> ([u8; 32], bool) {
+            let nonce_hash = self.compute_nonce_hash(&from, &nonce);
+            (nonce_hash, self.used_nonces.contains(nonce_hash))
+        }
+
+        /// `valid_until` of the authorization that consumed a nonce, or None if unused
+        ///
+        /// The nonce becomes prunable with `prune_expired_nonce` once this time
+        /// plus the expiry and late grace periods has passed. Canceled nonces
+        /// report `u64::MAX` and are never prunable.
+        #[ink(message)]
+        pub fn nonce_expiry(&self, from: AccountId, nonce: String) -> Option<u64> {
+            self.used_nonces.get(self.compute_nonce_hash(&from, &nonce))
+        }
+
+        /// Amount `account` has settled via authorizations in the current day bucket
+        #[ink(message)]
+        pub fn spent_today(&self, account: AccountId) -> Balance {
+            self.daily_spent.get((account, self.current_day())).unwrap_or(0)
+        }
+
+        /// Block number of the last authorization settled by or to `account`
+        #[ink(message)]
+        pub fn last_settled_block(&self, account: AccountId) -> Option<BlockNumber> {
+            self.last_settled_block.get(account)
+        }
+
+        /// Return the exact bytes an authorization signs (see `payment_message`)
+        ///
+        /// Purely derivational, for comparing client-side message construction
+        /// byte-for-byte with the contract. Under `SigningFormat::WrappedBytes`
+        /// this is the payload handed to the wallet, before it adds `<Bytes>`.
+        #[ink(message)]
+        pub fn message_preimage(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            nonce: String,
+            valid_until: u64,
+        ) -> Vec<u8> {
+            self.payment_message(from, to, amount, &nonce, valid_until)
+        }
+
+        /// Return the digest the signature is verified against under `scheme`
+        ///
+        /// Reflects the configured signing format.
+        #[ink(message)]
+        pub fn message_hash(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            nonce: String,
+            valid_until: u64,
+            scheme: SignatureScheme,
+        ) -> [u8; 32] {
+            let message = self.payment_message(from, to, amount, &nonce, valid_until);
+            Self::hash_message(&self.frame_message(&message), scheme)
+        }
+
+        /// Simulate settling a sequence of authorizations from `from`, in order
+        ///
+        /// Each step is `(amount, charge_fee)`, where `charge_fee: false` stands
+        /// for a fee-exempt recipient. Amounts and fees follow the same rules as
+        /// `transfer_with_authorization`, including `min_amount`, `min_fee` and
+        /// payer fee exemptions. The facilitator fee is carved out of `amount`,
+        /// so the payer is always debited the full `amount`; when `from` is
+        /// itself the fee recipient, the fee comes back to it unless it is
+        /// collected in a fee token. Returns the final balance, or the error of
+        /// the first step that could not settle. Spend limits and nonces are not
+        /// checked.
+        #[ink(message)]
+        pub fn simulate_sequence(
+            &self,
+            from: AccountId,
+            auths: Vec<(Balance, bool)>,
+        ) -> Result<Balance> {
+            let mut balance = self.balance_of(from);
+            for (amount, charge_fee) in auths {
+                self.ensure_settleable_amount(amount)?;
+                let facilitator_fee = self.settlement_fee(from, !charge_fee, amount, None);
+                balance = balance
+                    .checked_sub(amount)
+                    .ok_or(Error::PSP22(PSP22Error::InsufficientBalance))?;
+                if self.fee_token.is_none() && from == self.fee_recipient {
+                    balance = balance.saturating_add(facilitator_fee);
+                }
+            }
+            Ok(balance)
+        }
+
+        /// Relative verification cost hint for a signature scheme
+        ///
+        /// `scheme` is the SCALE discriminant of `SignatureScheme`
+        /// (0 = Sr25519, 1 = Ecdsa). Unknown schemes return 0.
+        #[ink(message)]
+        pub fn scheme_cost_hint(&self, scheme: u8) -> u32 {
+            match scheme {
+                0 => SR25519_COST_HINT,
+                1 => ECDSA_COST_HINT,
+                _ => 0,
+            }
+        }
+
+        /// Semantic version of the deployed contract logic, `CONTRACT_VERSION`
+        #[ink(message)]
+        pub fn version(&self) -> (u16, u16, u16) {
+            CONTRACT_VERSION
+        }
+
+        /// Cargo features this build was compiled with
+        #[ink(message)]
+        pub fn build_features(&self) -> Vec<String> {
+            let features = [
+                ("std", cfg!(feature = "std")),
+                ("ink-as-dependency", cfg!(feature = "ink-as-dependency")),
+                ("e2e-tests", cfg!(feature = "e2e-tests")),
+                ("debug-events", cfg!(feature = "debug-events")),
+            ];
+            features
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| String::from(*name))
+                .collect()
+        }
+
+        /// Which signature verification backend this build uses
+        ///
+        /// Always `SIGNATURE_BACKEND_HOST`: there is no software fallback, so
+        /// the contract requires a runtime exposing the sr25519/ECDSA host
+        /// functions. SDKs can use this to warn on incompatible chains.
+        #[ink(message)]
+        pub fn signature_backend(&self) -> u8 {
+            SIGNATURE_BACKEND_HOST
+        }
+
+        /// Cancel one of the caller's own authorizations before it is settled
+        ///
+        /// Marks the caller's `nonce` as used without moving funds, so any
+        /// authorization signed with it can never settle; a partially drawn
+        /// authorization loses its remaining capacity. The nonce is recorded as
+        /// never expiring, so `prune_expired_nonce` cannot revive it. Fails with
+        /// `NonceAlreadyUsed` if the nonce is already consumed.
+        ///
+        /// Only covers regular nonces; recipient-scoped, bytes32 and approval
+        /// nonces have their own cancel messages.
+        #[ink(message)]
+        pub fn cancel_authorization(&mut self, nonce: String) -> Result<()> {
+            Self::validate_nonce(&nonce)?;
+            let from = self.env().caller();
+            let nonce_hash = self.compute_nonce_hash(&from, &nonce);
+            self.cancel_nonce(from, nonce_hash, nonce)
+        }
+
+        /// `cancel_authorization` for a recipient-scoped nonce to `to`
+        #[ink(message)]
+        pub fn cancel_authorization_scoped(&mut self, to: AccountId, nonce: String) -> Result<()> {
+            Self::validate_nonce(&nonce)?;
+            let from = self.env().caller();
+            let nonce_hash = self.compute_nonce_hash_scoped(&from, &to, &nonce);
+            self.cancel_nonce(from, nonce_hash, nonce)
+        }
+
+        /// `cancel_authorization` for a bytes32 nonce
+        #[ink(message)]
+        pub fn cancel_authorization_b32(&mut self, nonce: [u8; 32]) -> Result<()> {
+            let from = self.env().caller();
+            let nonce_hash = self.compute_nonce_hash_b32(&from, &nonce);
+            self.cancel_nonce(from, nonce_hash, Self::hex_encode(&nonce))
+        }
+
+        /// `cancel_authorization` for an `approve_with_authorization` nonce
+        #[ink(message)]
+        pub fn cancel_approval(&mut self, nonce: String) -> Result<()> {
+            Self::validate_nonce(&nonce)?;
+            let owner = self.env().caller();
+            let nonce_hash = self.compute_nonce_hash_approval(&owner, &nonce);
+            self.cancel_nonce(owner, nonce_hash, nonce)
+        }
+
+        /// Whether the authorization with this nonce hash was canceled by its payer
+        ///
+        /// For relayers that only hold the nonce hash. Settled nonces are used
+        /// but not revoked.
+        #[ink(message)]
+        pub fn is_revoked_by_hash(&self, nonce_hash: [u8; 32]) -> bool {
+            self.canceled_nonces.contains(nonce_hash)
+        }
+
+        /// Remove a used nonce whose authorization has expired
+        ///
+        /// Pruning raises the payer's `pruned_through` watermark to the nonce's
+        /// `valid_until`, and every authorization from that payer valid until the
+        /// watermark or earlier is rejected with `PaymentExpired`. A pruned
+        /// authorization therefore stays dead even if the expiry grace, late
+        /// window or no-expiry setting is widened later. Nonces recorded with
+        /// `valid_until` 0 are never prunable, since enabling no-expiry would
+        /// make them valid again. Anyone may prune.
+        ///
+        /// The remaining capacity of an expired, partially drawn authorization
+        /// is pruned the same way. Recipient-scoped, bytes32 and approval
+        /// nonces have their own prune messages.
+        #[ink(message)]
+        pub fn prune_expired_nonce(&mut self, from: AccountId, nonce: String) -> Result<()> {
+            let nonce_hash = self.compute_nonce_hash(&from, &nonce);
+            self.prune_nonce(from, nonce_hash)
+        }
+
+        /// `prune_expired_nonce` for a recipient-scoped nonce to `to`
+        #[ink(message)]
+        pub fn prune_expired_nonce_scoped(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            nonce: String,
+        ) -> Result<()> {
+            let nonce_hash = self.compute_nonce_hash_scoped(&from, &to, &nonce);
+            self.prune_nonce(from, nonce_hash)
+        }
+
+        /// `prune_expired_nonce` for a bytes32 nonce
+        #[ink(message)]
+        pub fn prune_expired_nonce_b32(&mut self, from: AccountId, nonce: [u8; 32]) -> Result<()> {
+            let nonce_hash = self.compute_nonce_hash_b32(&from, &nonce);
+            self.prune_nonce(from, nonce_hash)
+        }
+
+        /// `prune_expired_nonce` for an `approve_with_authorization` nonce
+        #[ink(message)]
+        pub fn prune_expired_approval(&mut self, owner: AccountId, nonce: String) -> Result<()> {
+            let nonce_hash = self.compute_nonce_hash_approval(&owner, &nonce);
+            self.prune_nonce(owner, nonce_hash)
+        }
+
+        /// Highest `valid_until` among `from`'s pruned nonces, 0 if none
+        ///
+        /// Authorizations from `from` with a nonzero `valid_until` at or below
+        /// this are rejected with `PaymentExpired`.
+        #[ink(message)]
+        pub fn pruned_through(&self, from: AccountId) -> u64 {
+            self.pruned_through.get(from).unwrap_or(0)
+        }
+
+        /// Settle several authorizations, each independently
+        ///
+        /// Every item runs the checks of `transfer_with_authorization` plus a
+        /// balance check before any state changes, and its result is collected
+        /// in input order; a failing item leaves no state behind and does not
+        /// stop the rest. Nonces consumed by earlier items count, so a nonce
+        /// repeated within the batch settles only once. The call as a whole
+        /// fails, reverting every item, only if moving funds fails after an
+        /// item's checks passed: pulling a fee in `fee_token` fails, or a
+        /// recipient's balance would overflow. Such an item has already moved
+        /// funds, so it cannot be reported on its own. Fails with
+        /// `TooManyAuthorizations` for more than `MAX_BATCH_SIZE` items; callers
+        /// should split the batch. Ends with a `BatchSettled` summary.
+        #[ink(message)]
+        pub fn transfer_with_authorization_batch(
+            &mut self,
+            auths: Vec<Authorization>,
+        ) -> Result<Vec<Result<()>>> {
+            if auths.len() > MAX_BATCH_SIZE {
+                return Err(Error::TooManyAuthorizations);
+            }
+            let mut results = Vec::with_capacity(auths.len());
+            let mut summary = BatchSettled {
+                count_success: 0,
+                count_failed: 0,
+                total_net: 0,
+                total_fee: 0,
+            };
+            for auth in auths {
+                let checked = self.preflight_authorization(
+                    auth.from,
+                    auth.to,
+                    auth.amount,
+                    auth.valid_until,
+                    &auth.nonce,
+                    &auth.signature,
+                    auth.scheme,
+                    None,
+                );
+                match checked {
+                    Ok((nonce_hash, settlement)) => {
+                        summary.count_success = summary.count_success.saturating_add(1);
+                        summary.total_net = summary.total_net.saturating_add(settlement.net_amount);
+                        summary.total_fee =
+                            summary.total_fee.saturating_add(settlement.facilitator_fee);
+                        self.consume_nonce(auth.from, nonce_hash, auth.valid_until);
+                        self.settle(auth.from, auth.to, settlement, auth.nonce, nonce_hash)?;
+                        results.push(Ok(()));
+                    }
+                    Err(error) => {
+                        summary.count_failed = summary.count_failed.saturating_add(1);
+                        results.push(Err(error));
+                    }
+                }
+            }
+            self.env().emit_event(summary);
+            Ok(results)
+        }
+
+        /// Check whether a batch contains the same (from, nonce) pair more than once
+        ///
+        /// Only compares the authorizations against each other; storage is not read.
+        #[ink(message)]
+        pub fn batch_has_duplicate_nonces(&self, auths: Vec<Authorization>) -> bool {
+            let mut hashes: Vec<[u8; 32]> = auths
+                .iter()
+                .map(|auth| self.compute_nonce_hash(&auth.from, &auth.nonce))
+                .collect();
+            hashes.sort_unstable();
+            hashes.windows(2).any(|pair| pair[0] == pair[1])
+        }
+
+        // ============================================================
+        // ADMIN FUNCTIONS
+        // ============================================================
+
+        /// All admin-configurable settings in one read
+        ///
+        /// Per-account settings (roles, blocklist, fee exemptions, nonce
+        /// namespaces, multisig configs) have their own views.
+        #[ink(message)]
+        pub fn get_config(&self) -> ContractConfig {
+            ContractConfig {
+                owner: self.owner,
+                facilitator_fee_bps: self.effective_fee_bps(),
+                fee_rounding: self.fee_rounding,
+                fee_recipient: self.fee_recipient,
+                paused: self.paused,
+                max_fee_bps: self.max_fee_bps,
+                decimals: self.decimals,
+                authorizations_paused: self.authorizations_paused,
+                fee_token: self.fee_token,
+                expiry_grace_ms: self.expiry_grace_ms,
+                min_amount: self.min_amount,
+                min_fee: self.effective_min_fee(),
+                chain_id: self.chain_id,
+                allow_no_expiry: self.allow_no_expiry,
+                namespace_gating: self.namespace_gating,
+                owner_cannot_self_authorize: self.owner_cannot_self_authorize,
+                signing_format: self.signing_format,
+                spend_limit_per_day: self.spend_limit_per_day,
+                max_validity_window_ms: self.max_validity_window_ms,
+                max_submission_delay_ms: self.max_submission_delay_ms,
+                late_settlement_surcharge_bps: self.late_settlement_surcharge_bps,
+                allow_late_within_ms: self.allow_late_within_ms,
+                fee_timelock_ms: self.effective_fee_timelock_ms(),
+                pending_fee: self.pending_fee(),
+                pending_fee_timelock: self.pending_fee_timelock(),
+                pending_min_fee: self.pending_min_fee(),
+                cancel_on_freeze: self.cancel_on_freeze,
+                idempotency_window_ms: self.idempotency_window_ms,
+                max_supply: self.max_supply,
+            }
+        }
+
+        /// Get the contract owner
+        #[ink(message)]
+        pub fn get_owner(&self) -> AccountId {
+            self.owner
+        }
+
+        /// Get the owner nominated by `transfer_ownership`, if any
+        #[ink(message)]
+        pub fn get_pending_owner(&self) -> Option<AccountId> {
+            self.pending_owner
+        }
+
+        /// Nominate a new owner (only owner)
+        ///
+        /// Ownership only moves once `new_owner` calls `accept_ownership`. A
+        /// pending nomination can be overwritten by nominating again.
+        #[ink(message)]
+        pub fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.pending_owner = Some(new_owner);
+            Ok(())
+        }
+
+        /// Accept a pending ownership nomination (only the pending owner)
+        ///
+        /// Every role held by the previous owner moves to the new owner; roles
+        /// granted to other accounts are unaffected.
+        #[ink(message)]
+        pub fn accept_ownership(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            if self.pending_owner != Some(caller) {
+                return Err(Error::NotPendingOwner);
+            }
+            let previous_owner = self.owner;
+            self.revoke_all_roles(previous_owner);
+            self.grant_all_roles(caller);
+            self.owner = caller;
+            self.pending_owner = None;
+            self.env().emit_event(OwnershipTransferred {
+                previous_owner,
+                new_owner: caller,
+            });
+            Ok(())
+        }
+
+        /// Give up ownership permanently (only owner)
+        ///
+        /// The owner is set to the zero account, which no one controls, and the
+        /// owner's roles are revoked. Every owner-only message becomes
+        /// impossible afterwards; role-gated ones remain available to accounts
+        /// that were granted the role.
+        #[ink(message)]
+        pub fn renounce_ownership(&mut self) -> Result<()> {
+            self.ensure_owner()?;
+            let previous_owner = self.owner;
+            self.revoke_all_roles(previous_owner);
+            let new_owner = AccountId::from([0u8; 32]);
+            self.owner = new_owner;
+            self.pending_owner = None;
+            self.env().emit_event(OwnershipTransferred {
+                previous_owner,
+                new_owner,
+            });
+            Ok(())
+        }
+
+        /// Replace the contract code, keeping storage (only owner)
+        ///
+        /// **The new code must keep this contract's storage layout exactly:**
+        /// the same `Httpusd` fields in the same order with the same types, with
+        /// new fields only appended. Storage is not migrated, so a mismatched
+        /// layout decodes existing state as garbage or makes it undecodable.
+        #[ink(message)]
+        pub fn set_code_hash(&mut self, code_hash: [u8; 32]) -> Result<()> {
+            self.ensure_owner()?;
+            self.env()
+                .set_code_hash(&Hash::from(code_hash))
+                .map_err(|_| Error::CodeUpgradeFailed)?;
+            self.env().emit_event(CodeUpgraded {
+                code_hash,
+                upgraded_by: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Whether `account` holds `role`
+        #[ink(message)]
+        pub fn has_role(&self, account: AccountId, role: u8) -> bool {
+            self.roles.contains((account, role))
+        }
+
+        /// Grant `role` to `account` (only ADMIN)
+        #[ink(message)]
+        pub fn grant_role(&mut self, account: AccountId, role: u8) -> Result<()> {
+            self.ensure_role(ROLE_ADMIN)?;
+            self.roles.insert((account, role), &true);
+            self.env().emit_event(RoleGranted {
+                role,
+                account,
+                granted_by: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Revoke `role` from `account` (only ADMIN)
+        #[ink(message)]
+        pub fn revoke_role(&mut self, account: AccountId, role: u8) -> Result<()> {
+            self.ensure_role(ROLE_ADMIN)?;
+            self.roles.remove((account, role));
+            self.env().emit_event(RoleRevoked {
+                role,
+                account,
+                revoked_by: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Get the facilitator fee in basis points
+        ///
+        /// Includes a scheduled increase once its effective time has passed.
+        #[ink(message)]
+        pub fn get_facilitator_fee(&self) -> u16 {
+            self.effective_fee_bps()
+        }
+
+        /// Contract `recipient` has registered to be notified of its settlements
+        #[ink(message)]
+        pub fn get_payment_hook(&self, recipient: AccountId) -> Option<AccountId> {
+            self.payment_hooks.get(recipient)
+        }
+
+        /// Register or clear the contract notified of settlements to the caller
+        ///
+        /// After each settlement to the caller, `hook` receives
+        /// `on_payment_received(from, amount, nonce)` with the net amount. The
+        /// call may not re-enter this contract and runs with at most
+        /// `PAYMENT_HOOK_REF_TIME_LIMIT` ref time and
+        /// `PAYMENT_HOOK_PROOF_SIZE_LIMIT` proof size. A failing hook only emits
+        /// `PaymentHookFailed`; it never reverts the payment.
+        #[ink(message)]
+        pub fn set_payment_hook(&mut self, hook: Option<AccountId>) -> Result<()> {
+            let recipient = self.env().caller();
+            if let Some(hook) = hook {
+                self.payment_hooks.insert(recipient, &hook);
+            } else {
+                self.payment_hooks.remove(recipient);
+            }
+            Ok(())
+        }
+
+        /// The last `count` settlements, oldest first
+        ///
+        /// Only the last `PAYMENT_HISTORY_CAPACITY` settlements are retained, so
+        /// larger counts return at most that many.
+        #[ink(message)]
+        pub fn recent_payments(&self, count: u32) -> Vec<PaymentRecord> {
+            let retained = self.payment_history_count.min(PAYMENT_HISTORY_CAPACITY);
+            (1..=count.min(retained))
+                .rev()
+                .filter_map(|back| {
+                    let slot = (self.payment_history_count - back) % PAYMENT_HISTORY_CAPACITY;
+                    self.payment_history.get(slot)
+                })
+                .collect()
+        }
+
+        /// Scheduled fee increase as `(fee_bps, effective_at)`, if not yet in effect
+        #[ink(message)]
+        pub fn pending_fee(&self) -> Option<(u16, u64)> {
+            self.pending_fee
+                .filter(|(_, effective_at)| self.env().block_timestamp() < *effective_at)
+        }
+
+        /// Get the delay before a fee increase takes effect
+        #[ink(message)]
+        pub fn get_fee_timelock(&self) -> u64 {
+            self.effective_fee_timelock_ms()
+        }
+
+        /// Scheduled fee timelock reduction as `(timelock_ms, effective_at)`, if
+        /// not yet in effect
+        #[ink(message)]
+        pub fn pending_fee_timelock(&self) -> Option<(u64, u64)> {
+            self.pending_fee_timelock
+                .filter(|(_, effective_at)| self.env().block_timestamp() < *effective_at)
+        }
+
+        /// Set the delay before a fee increase takes effect (only owner)
+        ///
+        /// Only applies to increases scheduled afterwards. Zero, the default,
+        /// applies increases immediately. Longer timelocks apply immediately;
+        /// shorter ones are scheduled behind the current timelock, so lowering
+        /// it cannot be used to rush a fee increase through.
+        #[ink(message)]
+        pub fn set_fee_timelock(&mut self, timelock_ms: u64) -> Result<()> {
+            self.ensure_owner()?;
+            self.apply_pending_fee_timelock();
+            if timelock_ms < self.fee_timelock_ms {
+                let effective_at =
+                    self.env().block_timestamp().saturating_add(self.fee_timelock_ms);
+                self.pending_fee_timelock = Some((timelock_ms, effective_at));
+                self.emit_config_changed("pending_fee_timelock");
+                return Ok(());
+            }
+            self.pending_fee_timelock = None;
+            self.fee_timelock_ms = timelock_ms;
+            self.emit_config_changed("fee_timelock_ms");
+            Ok(())
+        }
+
+        /// Get the facilitator fee cap in basis points
+        #[ink(message)]
+        pub fn get_max_fee_bps(&self) -> u16 {
+            self.max_fee_bps
+        }
+
+        /// Facilitator fee that was in effect at `timestamp`
+        ///
+        /// Only the last `FEE_HISTORY_CAPACITY` changes are retained; returns None
+        /// for timestamps before the oldest retained record.
+        #[ink(message)]
+        pub fn fee_bps_at(&self, timestamp: u64) -> Option<u16> {
+            if let Some((fee_bps, effective_at)) = self.pending_fee {
+                if timestamp >= effective_at {
+                    return Some(fee_bps);
+                }
+            }
+            let retained = self.fee_history_count.min(FEE_HISTORY_CAPACITY);
+            (1..=retained)
+                .filter_map(|back| {
+                    let slot = (self.fee_history_count - back) % FEE_HISTORY_CAPACITY;
+                    self.fee_history.get(slot)
+                })
+                .find(|(changed_at, _)| *changed_at <= timestamp)
+                .map(|(_, fee_bps)| fee_bps)
+        }
+
+        /// Update facilitator fee (only FEE_MANAGER)
+        ///
+        /// Fails with `FeeTooHigh` above `max_fee_bps`. With a fee timelock set,
+        /// an increase is only scheduled: it becomes `pending_fee` and takes
+        /// effect `fee_timelock_ms` later, at the first settlement or fee change
+        /// after that. Decreases apply immediately and cancel any pending
+        /// increase.
+        #[ink(message)]
+        pub fn set_facilitator_fee(&mut self, fee_bps: u16) -> Result<()> {
+            self.ensure_role(ROLE_FEE_MANAGER)?;
+            if fee_bps > self.max_fee_bps {
+                return Err(Error::FeeTooHigh);
+            }
+            self.apply_pending_fee();
+            self.apply_pending_fee_timelock();
+            if fee_bps > self.facilitator_fee_bps && self.fee_timelock_ms > 0 {
+                let effective_at =
+                    self.env().block_timestamp().saturating_add(self.fee_timelock_ms);
+                self.pending_fee = Some((fee_bps, effective_at));
+                self.emit_config_changed("pending_fee");
+                return Ok(());
+            }
+            self.pending_fee = None;
+            let old_bps = self.facilitator_fee_bps;
+            self.facilitator_fee_bps = fee_bps;
+            self.record_fee_change();
+            self.env().emit_event(FeeChanged {
+                old_bps,
+                new_bps: fee_bps,
+                changed_by: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Whether an authorization valid until `valid_until` would now be
+        /// rejected with `PaymentExpired`, by the contract's own clock
+        ///
+        /// Accounts for the clock-skew grace, the late settlement grace period
+        /// (during which settlement still succeeds with a surcharge) and the
+        /// no-expiry sentinel.
+        #[ink(message)]
+        pub fn is_authorization_expired(&self, valid_until: u64) -> bool {
+            self.is_past_grace(valid_until)
+        }
+
+        /// How `valid_until` is interpreted, as `(kind, inclusive)`
+        ///
+        /// `kind` is always `EXPIRY_KIND_TIMESTAMP`. `inclusive` is true: an
+        /// authorization still settles in a block whose timestamp equals
+        /// `valid_until`. The clock-skew grace, late grace period and no-expiry
+        /// sentinel are reported by `get_expiry_grace`, `get_late_settlement`
+        /// and `get_allow_no_expiry`.
+        #[ink(message)]
+        pub fn expiry_semantics(&self) -> (u8, bool) {
+            (EXPIRY_KIND_TIMESTAMP, true)
+        }
+
+        /// Get the clock-skew grace applied past `valid_until`, in milliseconds
+        #[ink(message)]
+        pub fn get_expiry_grace(&self) -> u64 {
+            self.expiry_grace_ms
+        }
+
+        /// Set the clock-skew grace applied past `valid_until` (only owner)
+        ///
+        /// An authorization settles normally until `valid_until + grace_ms`.
+        /// Zero, the default, keeps expiry strict.
+        #[ink(message)]
+        pub fn set_expiry_grace(&mut self, grace_ms: u64) -> Result<()> {
+            self.ensure_owner()?;
+            self.expiry_grace_ms = grace_ms;
+            self.emit_config_changed("expiry_grace_ms");
+            Ok(())
+        }
+
+        /// Get the maximum delay between signing and settling a v2 authorization
+        #[ink(message)]
+        pub fn get_max_submission_delay(&self) -> Option<u64> {
+            self.max_submission_delay_ms
+        }
+
+        /// Set the maximum delay after `issued_at`, or None for no limit (only owner)
+        #[ink(message)]
+        pub fn set_max_submission_delay(&mut self, delay_ms: Option<u64>) -> Result<()> {
+            self.ensure_owner()?;
+            self.max_submission_delay_ms = delay_ms;
+            self.emit_config_changed("max_submission_delay_ms");
+            Ok(())
+        }
+
+        /// Get the maximum authorization validity window
+        #[ink(message)]
+        pub fn get_max_validity_window(&self) -> Option<u64> {
+            self.max_validity_window_ms
+        }
+
+        /// Limit how far in the future `valid_until` may be, or None for no limit (only owner)
+        ///
+        /// Authorizations valid for longer than `window_ms` from the current
+        /// block are rejected with `ValidityTooLong`, as are never-expiring ones.
+        #[ink(message)]
+        pub fn set_max_validity_window(&mut self, window_ms: Option<u64>) -> Result<()> {
+            self.ensure_owner()?;
+            self.max_validity_window_ms = window_ms;
+            self.emit_config_changed("max_validity_window_ms");
+            Ok(())
+        }
+
+        /// Get the late settlement surcharge (bps) and grace period (milliseconds)
+        #[ink(message)]
+        pub fn get_late_settlement(&self) -> (u16, u64) {
+            (self.late_settlement_surcharge_bps, self.allow_late_within_ms)
+        }
+
+        /// Configure late settlement (only owner)
+        ///
+        /// The grace period extends how long signed authorizations stay
+        /// spendable, so it is an owner decision like `set_expiry_grace`.
+        /// Authorizations submitted up to `allow_late_within_ms` after
+        /// `valid_until` still settle, paying `surcharge_bps` on top of the
+        /// facilitator fee. A zero grace period disables late settlement.
+        #[ink(message)]
+        pub fn set_late_settlement(
+            &mut self,
+            surcharge_bps: u16,
+            allow_late_within_ms: u64,
+        ) -> Result<()> {
+            self.ensure_owner()?;
+            if surcharge_bps > 10_000 {
+                return Err(Error::FeeTooHigh);
+            }
+            self.late_settlement_surcharge_bps = surcharge_bps;
+            self.allow_late_within_ms = allow_late_within_ms;
+            self.emit_config_changed("late_settlement");
+            Ok(())
+        }
+
+        /// Get the rounding applied to fractional facilitator fees
+        #[ink(message)]
+        pub fn get_fee_rounding(&self) -> FeeRounding {
+            self.fee_rounding
+        }
+
+        /// Get the framing applied to signed messages
+        #[ink(message)]
+        pub fn get_signing_format(&self) -> SigningFormat {
+            self.signing_format
+        }
+
+        /// Set the framing applied to signed messages (only owner)
+        ///
+        /// Switching formats invalidates outstanding authorizations signed
+        /// under the previous one.
+        #[ink(message)]
+        pub fn set_signing_format(&mut self, format: SigningFormat) -> Result<()> {
+            self.ensure_owner()?;
+            self.signing_format = format;
+            self.emit_config_changed("signing_format");
+            Ok(())
+        }
+
+        /// Whether the contract is paused
+        #[ink(message)]
+        pub fn is_paused(&self) -> bool {
+            self.paused
+        }
+
+        /// Halt all settlements (only PAUSER)
+        ///
+        /// Read-only messages keep working while paused.
+        #[ink(message)]
+        pub fn pause(&mut self) -> Result<()> {
+            self.ensure_role(ROLE_PAUSER)?;
+            self.paused = true;
+            self.env().emit_event(Paused {
+                account: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Resume settlements (only PAUSER)
+        #[ink(message)]
+        pub fn unpause(&mut self) -> Result<()> {
+            self.ensure_role(ROLE_PAUSER)?;
+            self.paused = false;
+            self.env().emit_event(Unpaused {
+                account: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Get the idempotency window in milliseconds
+        #[ink(message)]
+        pub fn get_idempotency_window(&self) -> u64 {
+            self.idempotency_window_ms
+        }
+
+        /// Set how long idempotency keys replay their recorded result (only owner)
+        #[ink(message)]
+        pub fn set_idempotency_window(&mut self, window_ms: u64) -> Result<()> {
+            self.ensure_owner()?;
+            self.idempotency_window_ms = window_ms;
+            self.emit_config_changed("idempotency_window_ms");
+            Ok(())
+        }
+
+        /// Get the per-payer daily spend limit
+        #[ink(message)]
+        pub fn get_spend_limit_per_day(&self) -> Option<Balance> {
+            self.spend_limit_per_day
+        }
+
+        /// Set the per-payer daily spend limit, or None to disable it (only owner)
+        ///
+        /// Days are `block_timestamp / DAY_MS` buckets, so usage resets at each
+        /// bucket boundary without any cleanup.
+        #[ink(message)]
+        pub fn set_spend_limit_per_day(&mut self, limit: Option<Balance>) -> Result<()> {
+            self.ensure_owner()?;
+            self.spend_limit_per_day = limit;
+            self.emit_config_changed("spend_limit_per_day");
+            Ok(())
+        }
+
+        /// Get the smallest amount an authorization may settle
+        #[ink(message)]
+        pub fn get_min_amount(&self) -> Balance {
+            self.min_amount
+        }
+
+        /// Set the smallest amount an authorization may settle (only owner)
+        ///
+        /// Smaller settlements fail with `AmountTooSmall`. Partial draws are
+        /// checked individually. Zero disables the minimum.
+        #[ink(message)]
+        pub fn set_min_amount(&mut self, min_amount: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            self.min_amount = min_amount;
+            self.emit_config_changed("min_amount");
+            Ok(())
+        }
+
+        /// Get the smallest facilitator fee charged on a non-exempt settlement
+        #[ink(message)]
+        pub fn get_min_fee(&self) -> Balance {
+            self.effective_min_fee()
+        }
+
+        /// Scheduled minimum fee increase as `(min_fee, effective_at)`, if not
+        /// yet in effect
+        #[ink(message)]
+        pub fn pending_min_fee(&self) -> Option<(Balance, u64)> {
+            self.pending_min_fee
+                .filter(|(_, effective_at)| self.env().block_timestamp() < *effective_at)
+        }
+
+        /// Set the smallest facilitator fee (only FEE_MANAGER)
+        ///
+        /// Settlements pay the larger of the percentage fee and `min_fee`, but
+        /// the floor never lifts the fee above what `max_fee_bps` allows on the
+        /// amount. Fee-exempt settlements still pay nothing. Zero disables the
+        /// floor. Like `set_facilitator_fee`, an increase waits out the fee
+        /// timelock as `pending_min_fee`; decreases apply immediately and cancel
+        /// any pending increase.
+        #[ink(message)]
+        pub fn set_min_fee(&mut self, min_fee: Balance) -> Result<()> {
+            self.ensure_role(ROLE_FEE_MANAGER)?;
+            self.apply_pending_min_fee();
+            self.apply_pending_fee_timelock();
+            if min_fee > self.min_fee && self.fee_timelock_ms > 0 {
+                let effective_at =
+                    self.env().block_timestamp().saturating_add(self.fee_timelock_ms);
+                self.pending_min_fee = Some((min_fee, effective_at));
+                self.emit_config_changed("pending_min_fee");
+                return Ok(());
+            }
+            self.pending_min_fee = None;
+            self.min_fee = min_fee;
+            self.emit_config_changed("min_fee");
+            Ok(())
+        }
+
+        /// Whether the owner is barred from authorizing payments from its own account
+        #[ink(message)]
+        pub fn get_owner_cannot_self_authorize(&self) -> bool {
+            self.owner_cannot_self_authorize
+        }
+
+        /// Bar the owner from settling its own authorizations (only owner)
+        ///
+        /// When enabled, the owner's funds can only move through explicit
+        /// PSP22 calls it signs as the caller.
+        #[ink(message)]
+        pub fn set_owner_cannot_self_authorize(&mut self, enabled: bool) -> Result<()> {
+            self.ensure_owner()?;
+            self.owner_cannot_self_authorize = enabled;
+            self.emit_config_changed("owner_cannot_self_authorize");
+            Ok(())
+        }
+
+        /// Whether signature-based transfers are paused
+        #[ink(message)]
+        pub fn is_authorizations_paused(&self) -> bool {
+            self.authorizations_paused
+        }
+
+        /// Pause `transfer_with_authorization` while leaving PSP22 transfers live (only PAUSER)
+        #[ink(message)]
+        pub fn pause_authorizations(&mut self) -> Result<()> {
+            self.ensure_role(ROLE_PAUSER)?;
+            self.authorizations_paused = true;
+            self.env().emit_event(AuthorizationsPaused {
+                account: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Resume signature-based transfers (only PAUSER)
+        #[ink(message)]
+        pub fn unpause_authorizations(&mut self) -> Result<()> {
+            self.ensure_role(ROLE_PAUSER)?;
+            self.authorizations_paused = false;
+            self.env().emit_event(AuthorizationsUnpaused {
+                account: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Get the chain id bound into signed authorizations
+        #[ink(message)]
+        pub fn get_chain_id(&self) -> u32 {
+            self.chain_id
+        }
+
+        /// Get the 32-byte domain separator bound into signed authorizations
+        #[ink(message)]
+        pub fn domain_separator(&self) -> [u8; 32] {
+            self.domain_separator
+        }
+
+        /// Get the account that receives facilitator fees
+        #[ink(message)]
+        pub fn get_fee_recipient(&self) -> AccountId {
+            self.fee_recipient
+        }
+
+        /// Whether payments to `recipient` are exempt from the facilitator fee
+        #[ink(message)]
+        pub fn is_fee_exempt(&self, recipient: AccountId) -> bool {
+            self.recipient_fee_exempt.contains(recipient)
+        }
+
+        /// Waive or restore the facilitator fee on payments to `recipient` (only FEE_MANAGER)
+        ///
+        /// The exemption applies regardless of the sender, and also waives any
+        /// late settlement surcharge.
+        #[ink(message)]
+        pub fn set_fee_exempt(&mut self, recipient: AccountId, exempt: bool) -> Result<()> {
+            self.ensure_role(ROLE_FEE_MANAGER)?;
+            if exempt {
+                self.recipient_fee_exempt.insert(recipient, &true);
+            } else {
+                self.recipient_fee_exempt.remove(recipient);
+            }
+            self.emit_config_changed("recipient_fee_exempt");
+            Ok(())
+        }
+
+        /// Whether payments from `payer` are exempt from the facilitator fee
+        #[ink(message)]
+        pub fn is_payer_fee_exempt(&self, payer: AccountId) -> bool {
+            self.payer_fee_exempt.contains(payer)
+        }
+
+        /// Waive or restore the facilitator fee on payments from `payer` (only FEE_MANAGER)
+        ///
+        /// Meant for partners and internal accounts. The exemption applies
+        /// regardless of the recipient, and also waives any late settlement
+        /// surcharge.
+        #[ink(message)]
+        pub fn set_payer_fee_exempt(&mut self, payer: AccountId, exempt: bool) -> Result<()> {
+            self.ensure_role(ROLE_FEE_MANAGER)?;
+            if exempt {
+                self.payer_fee_exempt.insert(payer, &true);
+            } else {
+                self.payer_fee_exempt.remove(payer);
+            }
+            self.emit_config_changed("payer_fee_exempt");
+            Ok(())
+        }
+
+        /// Update the account that receives facilitator fees (only FEE_MANAGER)
+        ///
+        /// Fails with `InvalidFeeRecipient` for the zero account.
+        #[ink(message)]
+        pub fn set_fee_recipient(&mut self, recipient: AccountId) -> Result<()> {
+            self.ensure_role(ROLE_FEE_MANAGER)?;
+            if recipient == AccountId::from([0u8; 32]) {
+                return Err(Error::InvalidFeeRecipient);
+            }
+            self.fee_recipient = recipient;
+            self.emit_config_changed("fee_recipient");
+            Ok(())
+        }
+
+        /// Get the token contract facilitator fees are collected in
+        #[ink(message)]
+        pub fn get_fee_token(&self) -> Option<AccountId> {
+            self.fee_token
+        }
+
+        /// Collect fees in a separate token, or None to skim them from the
+        /// payment (only FEE_MANAGER)
+        ///
+        /// With a fee token set, the recipient receives the full authorized
+        /// amount and the fee is pulled from the payer with `transfer_from` on
+        /// the fee token, so payers must approve this contract there.
+        #[ink(message)]
+        pub fn set_fee_token(&mut self, fee_token: Option<AccountId>) -> Result<()> {
+            self.ensure_role(ROLE_FEE_MANAGER)?;
+            self.fee_token = fee_token;
+            self.emit_config_changed("fee_token");
+            Ok(())
+        }
+
+        /// Whether `valid_until == 0` is accepted as "never expires"
+        #[ink(message)]
+        pub fn get_allow_no_expiry(&self) -> bool {
+            self.allow_no_expiry
+        }
+
+        /// Enable or disable the `valid_until == 0` no-expiry sentinel (only owner)
+        ///
+        /// Disabled by default, in which case a zero `valid_until` is always expired.
+        #[ink(message)]
+        pub fn set_allow_no_expiry(&mut self, allow: bool) -> Result<()> {
+            self.ensure_owner()?;
+            self.allow_no_expiry = allow;
+            self.emit_config_changed("allow_no_expiry");
+            Ok(())
+        }
+
+        /// Whether nonce hashes must fall under a registered namespace
+        #[ink(message)]
+        pub fn get_namespace_gating(&self) -> bool {
+            self.namespace_gating
+        }
+
+        /// Check whether a nonce hash prefix is a registered namespace
+        #[ink(message)]
+        pub fn is_namespace_registered(&self, prefix: u8) -> bool {
+            self.nonce_namespaces.get(prefix).unwrap_or(false)
+        }
+
+        /// Enable or disable nonce namespace gating (only owner)
+        ///
+        /// When enabled, authorizations whose nonce hash does not start with a
+        /// registered prefix byte are rejected with `UnknownNamespace`.
+        #[ink(message)]
+        pub fn set_namespace_gating(&mut self, enabled: bool) -> Result<()> {
+            self.ensure_owner()?;
+            self.namespace_gating = enabled;
+            self.emit_config_changed("namespace_gating");
+            Ok(())
+        }
+
+        /// Register a nonce hash prefix as a namespace (only owner)
+        #[ink(message)]
+        pub fn register_namespace(&mut self, prefix: u8) -> Result<()> {
+            self.ensure_owner()?;
+            self.nonce_namespaces.insert(prefix, &true);
+            self.emit_config_changed("nonce_namespaces");
+            Ok(())
+        }
+
+        /// Remove a registered nonce namespace (only owner)
+        #[ink(message)]
+        pub fn unregister_namespace(&mut self, prefix: u8) -> Result<()> {
+            self.ensure_owner()?;
+            self.nonce_namespaces.remove(prefix);
+            self.emit_config_changed("nonce_namespaces");
+            Ok(())
+        }
+
+        /// Whether an account is on the blocklist
+        #[ink(message)]
+        pub fn is_blocked(&self, account: AccountId) -> bool {
+            self.blocklist.contains(account)
+        }
+
+        /// Add or remove an account from the blocklist (only BLOCKLIST_MANAGER)
+        ///
+        /// Blocked accounts can neither pay nor be paid through authorizations.
+        /// With `cancel_on_freeze` enabled, blocking also bumps the account's
+        /// authorization epoch, so authorizations it signed earlier stay invalid
+        /// after it is unblocked.
+        #[ink(message)]
+        pub fn set_blocked(&mut self, account: AccountId, blocked: bool) -> Result<()> {
+            self.ensure_role(ROLE_BLOCKLIST_MANAGER)?;
+            if blocked {
+                if self.cancel_on_freeze && !self.is_blocked(account) {
+                    let epoch = self.authorization_epoch(account).saturating_add(1);
+                    self.authorization_epochs.insert(account, &epoch);
+                }
+                self.blocklist.insert(account, &true);
+            } else {
+                self.blocklist.remove(account);
+            }
+            self.emit_config_changed("blocklist");
+            Ok(())
+        }
+
+        /// Whether blocking an account voids its outstanding authorizations
+        #[ink(message)]
+        pub fn get_cancel_on_freeze(&self) -> bool {
+            self.cancel_on_freeze
+        }
+
+        /// Enable or disable voiding authorizations on freeze (only owner)
+        #[ink(message)]
+        pub fn set_cancel_on_freeze(&mut self, enabled: bool) -> Result<()> {
+            self.ensure_owner()?;
+            self.cancel_on_freeze = enabled;
+            self.emit_config_changed("cancel_on_freeze");
+            Ok(())
+        }
+
+        /// Authorization epoch of a payer
+        ///
+        /// A nonzero epoch is appended to the signed message (u32 LE), so
+        /// authorizations signed under an earlier epoch no longer verify. Epoch 0
+        /// adds nothing, leaving the message unchanged for never-frozen accounts.
+        #[ink(message)]
+        pub fn authorization_epoch(&self, account: AccountId) -> u32 {
+            self.authorization_epochs.get(account).unwrap_or(0)
+        }
+
+        /// Update the token name (only owner)
+        #[ink(message)]
+        pub fn set_token_name(&mut self, name: String) -> Result<()> {
+            self.ensure_owner()?;
+            if name.len() > MAX_METADATA_LEN {
+                return Err(Error::MetadataTooLong);
+            }
+            self.token_name = Some(name);
+            self.emit_metadata_changed();
+            Ok(())
+        }
+
+        /// Update the token symbol (only owner)
+        #[ink(message)]
+        pub fn set_token_symbol(&mut self, symbol: String) -> Result<()> {
+            self.ensure_owner()?;
+            if symbol.len() > MAX_METADATA_LEN {
+                return Err(Error::MetadataTooLong);
+            }
+            self.token_symbol = Some(symbol);
+            self.emit_metadata_changed();
+            Ok(())
+        }
+
+        /// Check the configuration for cross-field consistency
+        ///
+        /// Intended for operators after a round of owner changes. Returns the
+        /// first inconsistency found as its specific error, or Ok(()). A
+        /// minimum fee counts as too high when `max_fee_bps` of `min_amount`
+        /// falls short of it, since the smallest settlements would then be
+        /// charged less than the floor.
+        #[ink(message)]
+        pub fn validate_config(&self) -> Result<()> {
+            if self.max_fee_bps > 10_000 || self.facilitator_fee_bps > self.max_fee_bps {
+                return Err(Error::FeeTooHigh);
+            }
+            if self.pending_fee.is_some_and(|(fee_bps, _)| fee_bps > self.max_fee_bps) {
+                return Err(Error::FeeTooHigh);
+            }
+            let max_fee_on_min_amount = self.bps_fee(self.min_amount, self.max_fee_bps);
+            let min_fee = self.min_fee.max(self.pending_min_fee.map_or(0, |(min_fee, _)| min_fee));
+            if min_fee > max_fee_on_min_amount {
+                return Err(Error::FeeTooHigh);
+            }
+            if self.facilitator_fee_bps > 0 && self.fee_recipient == AccountId::from([0u8; 32]) {
+                return Err(Error::InvalidFeeRecipient);
+            }
+            if self.spend_limit_per_day == Some(0) {
+                return Err(Error::InvalidSpendLimit);
+            }
+            if self.spend_limit_per_day.is_some_and(|limit| self.min_amount > limit) {
+                return Err(Error::InvalidSpendLimit);
+            }
+            if self.max_supply.is_some_and(|cap| self.total_supply > cap) {
+                return Err(Error::MaxSupplyExceeded);
+            }
+            Ok(())
+        }
+
+        // ============================================================
+        // PRIVATE HELPER FUNCTIONS
+        // ============================================================
+
+        /// Ensure the caller is the contract owner
+        fn ensure_owner(&self) -> Result<()> {
+            if self.env().caller() != self.owner {
+                return Err(Error::NotOwner);
+            }
+            Ok(())
+        }
+
+        /// Ensure the caller holds `role`
+        fn ensure_role(&self, role: u8) -> Result<()> {
+            if !self.has_role(self.env().caller(), role) {
+                return Err(Error::MissingRole);
+            }
+            Ok(())
+        }
+
+        /// Drop an `approve_until` allowance whose expiry has passed, with its expiry
+        fn clear_expired_allowance(&mut self, owner: AccountId, spender: AccountId) {
+            let expired = self
+                .allowance_expiries
+                .get((owner, spender))
+                .is_some_and(|valid_until| self.env().block_timestamp() > valid_until);
+            if expired {
+                self.allowances.remove((owner, spender));
+                self.allowance_expiries.remove((owner, spender));
+            }
+        }
+
+        /// Grant every role to an incoming owner
+        fn grant_all_roles(&mut self, account: AccountId) {
+            for role in ALL_ROLES {
+                self.roles.insert((account, role), &true);
+            }
+        }
+
+        /// Revoke every role from an outgoing owner
+        fn revoke_all_roles(&mut self, account: AccountId) {
+            for role in ALL_ROLES {
+                self.roles.remove((account, role));
+            }
+        }
+
+        /// Facilitator fee in effect now, including a due scheduled increase
+        fn effective_fee_bps(&self) -> u16 {
+            match self.pending_fee {
+                Some((fee_bps, effective_at)) if self.env().block_timestamp() >= effective_at => {
+                    fee_bps
+                }
+                _ => self.facilitator_fee_bps,
+            }
+        }
+
+        /// Fee timelock in effect now, including a due scheduled reduction
+        fn effective_fee_timelock_ms(&self) -> u64 {
+            match self.pending_fee_timelock {
+                Some((timelock_ms, effective_at))
+                    if self.env().block_timestamp() >= effective_at =>
+                {
+                    timelock_ms
+                }
+                _ => self.fee_timelock_ms,
+            }
+        }
+
+        /// Minimum fee in effect now, including a due scheduled increase
+        fn effective_min_fee(&self) -> Balance {
+            match self.pending_min_fee {
+                Some((min_fee, effective_at)) if self.env().block_timestamp() >= effective_at => {
+                    min_fee
+                }
+                _ => self.min_fee,
+            }
+        }
+
+        /// Commit a scheduled minimum fee increase whose effective time has passed
+        fn apply_pending_min_fee(&mut self) {
+            let Some((min_fee, effective_at)) = self.pending_min_fee else {
+                return;
+            };
+            if self.env().block_timestamp() < effective_at {
+                return;
+            }
+            self.min_fee = min_fee;
+            self.pending_min_fee = None;
+        }
+
+        /// Commit a scheduled fee timelock reduction whose effective time has passed
+        fn apply_pending_fee_timelock(&mut self) {
+            let Some((timelock_ms, effective_at)) = self.pending_fee_timelock else {
+                return;
+            };
+            if self.env().block_timestamp() < effective_at {
+                return;
+            }
+            self.fee_timelock_ms = timelock_ms;
+            self.pending_fee_timelock = None;
+        }
+
+        /// Commit a scheduled fee increase whose effective time has passed
+        ///
+        /// The change is recorded at its effective time and `FeeChanged` is
+        /// attributed to the caller whose call applied it.
+        fn apply_pending_fee(&mut self) {
+            let Some((fee_bps, effective_at)) = self.pending_fee else {
+                return;
+            };
+            if self.env().block_timestamp() < effective_at {
+                return;
+            }
+            let old_bps = self.facilitator_fee_bps;
+            self.facilitator_fee_bps = fee_bps;
+            self.pending_fee = None;
+            self.record_fee_change_at(effective_at);
+            self.env().emit_event(FeeChanged {
+                old_bps,
+                new_bps: fee_bps,
+                changed_by: self.env().caller(),
+            });
+        }
+
+        /// Append the current fee to the fee history ring
+        fn record_fee_change(&mut self) {
+            self.record_fee_change_at(self.env().block_timestamp());
+        }
+
+        /// Append the current fee to the fee history ring as changed at `timestamp`
+        ///
+        /// A second change at the same timestamp overwrites the first.
+        fn record_fee_change_at(&mut self, timestamp: u64) {
+            let record = (timestamp, self.facilitator_fee_bps);
+            if self.fee_history_count > 0 {
+                let last = (self.fee_history_count - 1) % FEE_HISTORY_CAPACITY;
+                let same_time = |(changed_at, _): (u64, u16)| changed_at == timestamp;
+                if self.fee_history.get(last).is_some_and(same_time) {
+                    self.fee_history.insert(last, &record);
+                    return;
+                }
+            }
+            let slot = self.fee_history_count % FEE_HISTORY_CAPACITY;
+            self.fee_history.insert(slot, &record);
+            self.fee_history_count = self.fee_history_count.saturating_add(1);
+        }
+
+        /// Append a settlement to the payment history ring, overwriting the oldest
+        fn record_payment(&mut self, record: PaymentRecord) {
+            let slot = self.payment_history_count % PAYMENT_HISTORY_CAPACITY;
+            self.payment_history.insert(slot, &record);
+            self.payment_history_count = self.payment_history_count.saturating_add(1);
+        }
+
+        /// Verify and settle a full authorization
+        ///
+        /// `issued_at` selects the v2 signed message; None verifies the v1 one.
+        #[allow(clippy::too_many_arguments)]
+        fn execute_authorization(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            valid_until: u64,
+            nonce: String,
+            signature: Vec<u8>,
+            scheme: SignatureScheme,
+            issued_at: Option<u64>,
+        ) -> Result<()> {
+            let (nonce_hash, settlement) = self.preflight_authorization(
+                from,
+                to,
+                amount,
+                valid_until,
+                &nonce,
+                &signature,
+                scheme,
+                issued_at,
+            )?;
+
+            // 5. Mark nonce as used BEFORE transfer (prevent reentrancy)
+            self.consume_nonce(from, nonce_hash, valid_until);
+
+            // 6. Move funds, record and emit
+            self.settle(from, to, settlement, nonce, nonce_hash)
+        }
+
+        /// Every check `execute_authorization` makes before mutating state
+        ///
+        /// Returns the nonce hash to consume and the computed fee split.
+        #[allow(clippy::too_many_arguments)]
+        fn preflight_authorization(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            valid_until: u64,
+            nonce: &str,
+            signature: &[u8],
+            scheme: SignatureScheme,
+            issued_at: Option<u64>,
+        ) -> Result<([u8; 32], Settlement)> {
+            let (nonce_hash, late_by_ms) =
+                self.authorization_preconditions(from, to, valid_until, nonce)?;
+            self.ensure_within_supply(amount)?;
+
+            // 3. Verify signature over the v1 or v2 (issued_at) message; a
+            //    multisig payer's own key alone never meets its threshold
+            if self.multisig_configs.contains(from) {
+                return Err(Error::ThresholdNotMet);
+            }
+            let message = match issued_at {
+                Some(issued_at) => {
+                    self.payment_message_v2(from, to, amount, nonce, valid_until, issued_at)
+                }
+                None => self.payment_message(from, to, amount, nonce, valid_until),
+            };
+            if !self.verify_signed_message(from, &message, signature, scheme) {
+                return Err(Error::InvalidSignature);
+            }
+
+            // 4. Validate amount and calculate facilitator fee
+            let settlement = self.prepare_settlement(from, to, amount, late_by_ms)?;
+
+            Ok((nonce_hash, settlement))
+        }
+
+        /// Checks made before an authorization's signature is verified
+        ///
+        /// Returns the nonce hash and, for late settlements, how late it is.
+        fn authorization_preconditions(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            valid_until: u64,
+            nonce: &str,
+        ) -> Result<([u8; 32], Option<u64>)> {
+            self.ensure_authorizations_open(from)?;
+            Self::ensure_nonzero_parties(from, to)?;
+
+            // 0. Validate the nonce before it is hashed anywhere, so oversized
+            //    nonces fail without paying for hashing the signed message
+            Self::validate_nonce(nonce)?;
+            let nonce_hash = self.compute_nonce_hash(&from, nonce);
+            self.ensure_not_blocked(from, to, nonce, nonce_hash)?;
+
+            // 1. Check if payment has expired (allowing the late grace period)
+            let late_by_ms = self.check_expiry(from, valid_until)?;
+
+            // 2. Check if nonce has been used (prevent replay attacks)
+            self.ensure_nonce_available(nonce_hash)?;
+            if self.partial_remaining.contains(nonce_hash) {
+                return Err(Error::NonceAlreadyUsed);
+            }
+
+            Ok((nonce_hash, late_by_ms))
+        }
+
+        /// Checks shared by every authorization entry point
+        fn ensure_authorizations_open(&self, from: AccountId) -> Result<()> {
+            self.when_not_paused()?;
+            if self.authorizations_paused {
+                return Err(Error::AuthorizationsPaused);
+            }
+            if self.owner_cannot_self_authorize && from == self.owner {
+                return Err(Error::OwnerSelfAuthorization);
+            }
+            Ok(())
+        }
+
+        /// Reject amounts above total supply before any costlier checks
+        ///
+        /// Such an amount could never be covered by a balance anyway, so this is
+        /// only a fast-fail for gas, not a security control.
+        fn ensure_within_supply(&self, amount: Balance) -> Result<()> {
+            if amount > self.total_supply {
+                return Err(Error::AmountExceedsSupply);
+            }
+            Ok(())
+        }
+
+        /// Reject the zero account as sender or recipient, which would burn tokens
+        fn ensure_nonzero_parties(from: AccountId, to: AccountId) -> Result<()> {
+            let zero = AccountId::from([0u8; 32]);
+            if from == zero {
+                return Err(Error::PSP22(PSP22Error::ZeroSenderAddress));
+            }
+            if to == zero {
+                return Err(Error::PSP22(PSP22Error::ZeroRecipientAddress));
+            }
+            Ok(())
+        }
+
+        /// Reject payments involving a blocked account, emitting PaymentFailed
+        fn ensure_not_blocked(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            nonce: &str,
+            nonce_hash: [u8; 32],
+        ) -> Result<()> {
+            if self.is_blocked(from) || self.is_blocked(to) {
+                self.env().emit_event(PaymentFailed {
+                    from,
+                    to,
+                    nonce_hash,
+                    nonce: String::from(nonce),
+                    reason: String::from("blocked"),
+                });
+                return Err(Error::AccountBlocked);
+            }
+            Ok(())
+        }
+
+        /// Reject nonces outside a registered namespace or already used
+        fn ensure_nonce_available(&self, nonce_hash: [u8; 32]) -> Result<()> {
+            if self.namespace_gating && !self.is_namespace_registered(nonce_hash[0]) {
+                return Err(Error::UnknownNamespace);
+            }
+            if self.used_nonces.contains(nonce_hash) {
+                return Err(Error::NonceAlreadyUsed);
+            }
+            Ok(())
+        }
+
+        /// Mark a nonce as used and announce it
+        fn consume_nonce(&mut self, from: AccountId, nonce_hash: [u8; 32], valid_until: u64) {
+            self.used_nonces.insert(nonce_hash, &valid_until);
+            let count = self.used_nonce_count(from).saturating_add(1);
+            self.used_nonce_counts.insert(from, &count);
+            self.env().emit_event(NonceConsumed {
+                from,
+                nonce_hash,
+                block_number: self.env().block_number(),
+            });
+        }
+
+        /// Validate an authorized amount and compute its fee split
+        ///
+        /// Late settlements pay `late_settlement_surcharge_bps` on top of the
+        /// facilitator fee, capped at 100%. Payments from fee-exempt payers or
+        /// to fee-exempt recipients pay no fee at all. With a fee token set,
+        /// the fee is not deducted and `net_amount` is the full `amount`. Either
+        /// way the payer must hold `amount` up front, so a settlement never
+        /// moves the net amount and then finds the fee uncovered.
+        fn prepare_settlement(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            late_by_ms: Option<u64>,
+        ) -> Result<Settlement> {
+            self.ensure_settleable_amount(amount)?;
+            if self.balance_of(from) < amount {
+                return Err(Error::PSP22(PSP22Error::InsufficientBalance));
+            }
+            let spent_today = self.check_spend_limit(from, amount)?;
+
+            let facilitator_fee =
+                self.settlement_fee(from, self.is_fee_exempt(to), amount, late_by_ms);
+
+            let net_amount = if self.fee_token.is_some() {
+                amount
+            } else {
+                amount
+                    .checked_sub(facilitator_fee)
+                    .ok_or(Error::PSP22(PSP22Error::InsufficientBalance))?
+            };
+
+            Ok(Settlement {
+                facilitator_fee,
+                net_amount,
+                spent_today,
+                late_by_ms,
+            })
+        }
+
+        /// Reject a zero amount or one below `min_amount`
+        fn ensure_settleable_amount(&self, amount: Balance) -> Result<()> {
+            if amount == 0 {
+                return Err(Error::PSP22(PSP22Error::InsufficientBalance));
+            }
+            if amount < self.min_amount {
+                return Err(Error::AmountTooSmall);
+            }
+            Ok(())
+        }
+
+        /// Facilitator fee on a settlement of `amount` from `from`
+        ///
+        /// Zero if the payer or the recipient is fee-exempt; otherwise the
+        /// current fee, plus the late surcharge for late settlements.
+        fn settlement_fee(
+            &self,
+            from: AccountId,
+            recipient_exempt: bool,
+            amount: Balance,
+            late_by_ms: Option<u64>,
+        ) -> Balance {
+            if recipient_exempt || self.is_payer_fee_exempt(from) {
+                return 0;
+            }
+            let fee_bps = match late_by_ms {
+                Some(_) => self
+                    .effective_fee_bps()
+                    .saturating_add(self.late_settlement_surcharge_bps)
+                    .min(10_000),
+                None => self.effective_fee_bps(),
+            };
+            self.compute_fee(amount, fee_bps)
+        }
+
+        /// Fee on `amount` at `fee_bps`, rounded per `fee_rounding` and raised to
+        /// at least `min_fee`
+        ///
+        /// The floor is capped at the fee `max_fee_bps` allows on `amount`, so a
+        /// minimum fee never charges more than the fee cap would. The fee never
+        /// exceeds `amount`.
+        fn compute_fee(&self, amount: Balance, fee_bps: u16) -> Balance {
+            let floor = self.effective_min_fee().min(self.bps_fee(amount, self.max_fee_bps));
+            self.bps_fee(amount, fee_bps).max(floor).min(amount)
+        }
+
+        /// Fee on `amount` at `fee_bps`, rounded per `fee_rounding`
+        ///
+        /// `amount` is split into whole multiples of 10000 and a remainder so no
+        /// intermediate product can overflow.
+        fn bps_fee(&self, amount: Balance, fee_bps: u16) -> Balance {
+            let fee_bps = Balance::from(fee_bps);
+            let whole = (amount / 10_000).saturating_mul(fee_bps);
+            let scaled_remainder = (amount % 10_000) * fee_bps;
+            let fee = whole.saturating_add(scaled_remainder / 10_000);
+            let fraction = scaled_remainder % 10_000;
+            let round_up = match self.fee_rounding {
+                FeeRounding::Down => false,
+                FeeRounding::Up => fraction > 0,
+                FeeRounding::HalfUp => fraction >= 5_000,
+            };
+            if round_up {
+                fee.saturating_add(1)
+            } else {
+                fee
+            }
+        }
+
+        /// Move an authorized payment, record it and emit TransferWithAuthorization
+        ///
+        /// The authorization must already be verified and its nonce consumed.
+        /// Events are emitted in this order: `Transfer` for the net amount,
+        /// `Transfer` for the fee only if it is nonzero (or `FeeTransferFailed` if
+        /// moving it fails), `LateSettlement` for late settlements, then
+        /// `TransferWithAuthorization`.
+        fn settle(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            settlement: Settlement,
+            nonce: String,
+            nonce_hash: [u8; 32],
+        ) -> Result<()> {
+            // The fee split already used any due scheduled fee; make it permanent
+            self.apply_pending_fee();
+            self.apply_pending_min_fee();
+
+            // Execute transfer from 'from' to 'to'
+            self.transfer_from_to(from, to, settlement.net_amount)?;
+            let refundable = self.refundable.get((nonce_hash, to)).unwrap_or(0);
+            let refundable = refundable
+                .checked_add(settlement.net_amount)
+                .ok_or(Error::PSP22(PSP22Error::Overflow))?;
+            self.refundable.insert((nonce_hash, to), &refundable);
+
+            // Transfer fee to the fee recipient
+            let mut fee_collected = 0;
+            if let Some(fee_token) = self.fee_token {
+                if settlement.facilitator_fee > 0 {
+                    self.pull_fee(fee_token, from, settlement.facilitator_fee)?;
+                    fee_collected = settlement.facilitator_fee;
+                }
+            } else if settlement.facilitator_fee > 0 {
+                // The payer's balance was checked up front; should moving the fee
+                // still fail after the net amount has moved, the shortfall is
+                // reported, not reverted
+                match self.transfer_from_to(from, self.fee_recipient, settlement.facilitator_fee) {
+                    Ok(()) => fee_collected = settlement.facilitator_fee,
+                    Err(_) => self.env().emit_event(FeeTransferFailed {
+                        from,
+                        fee_recipient: self.fee_recipient,
+                        nonce_hash,
+                        shortfall: settlement.facilitator_fee,
+                    }),
+                }
+            }
+
+            // Update the running totals
+            self.total_volume_settled = self
+                .total_volume_settled
+                .checked_add(settlement.net_amount)
+                .ok_or(Error::PSP22(PSP22Error::Overflow))?;
+            self.total_fees_collected = self
+                .total_fees_collected
+                .checked_add(fee_collected)
+                .ok_or(Error::PSP22(PSP22Error::Overflow))?;
+
+            // Record settlement block for both parties and the payer's daily spend
+            let block_number = self.env().block_number();
+            self.last_settled_block.insert(from, &block_number);
+            self.last_settled_block.insert(to, &block_number);
+            self.daily_spent.insert((from, self.current_day()), &settlement.spent_today);
+            self.record_payment(PaymentRecord {
+                from,
+                to,
+                net_amount: settlement.net_amount,
+                fee: fee_collected,
+                nonce_hash,
+                block_number,
+            });
+
+            if let Some(late_by_ms) = settlement.late_by_ms {
+                self.env().emit_event(LateSettlement {
+                    from,
+                    to,
+                    nonce: nonce.clone(),
+                    late_by_ms,
+                    surcharge_bps: self.late_settlement_surcharge_bps,
+                });
+            }
+
+            self.env().emit_event(TransferWithAuthorization {
+                from,
+                to,
+                nonce_hash,
+                amount: settlement.net_amount,
+                facilitator_fee: settlement.facilitator_fee,
+                nonce: nonce.clone(),
+            });
+
+            if let Some(hook) = self.payment_hooks.get(to) {
+                self.notify_payment_hook(hook, from, to, settlement.net_amount, nonce);
+            }
+
+            Ok(())
+        }
+
+        /// Call `on_payment_received` on a recipient's hook contract
+        ///
+        /// The hook runs under fixed ref-time and proof-size limits, so it cannot
+        /// spend the gas the rest of the settlement needs. When less than its
+        /// budget plus `PAYMENT_HOOK_GAS_RESERVE` is left, the hook is skipped.
+        /// Any failure is reported with `PaymentHookFailed` and otherwise ignored.
+        fn notify_payment_hook(
+            &self,
+            hook: AccountId,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            nonce: String,
+        ) {
+            use ink::env::call::{build_call, ExecutionInput, Selector};
+            // Skipped (None) without enough headroom for the hook's full budget
+            let needed = PAYMENT_HOOK_REF_TIME_LIMIT.saturating_add(PAYMENT_HOOK_GAS_RESERVE);
+            let result = (self.env().gas_left() >= needed).then(|| {
+                build_call::<ink::env::DefaultEnvironment>()
+                    .call(hook)
+                    .ref_time_limit(PAYMENT_HOOK_REF_TIME_LIMIT)
+                    .proof_size_limit(PAYMENT_HOOK_PROOF_SIZE_LIMIT)
+                    .exec_input(
+                        ExecutionInput::new(Selector::new(ink::selector_bytes!(
+                            "on_payment_received"
+                        )))
+                        .push_arg(from)
+                        .push_arg(amount)
+                        .push_arg(&nonce),
+                    )
+                    .returns::<()>()
+                    .try_invoke()
+            });
+            if !matches!(result, Some(Ok(Ok(())))) {
+                self.env().emit_event(PaymentHookFailed {
+                    recipient: to,
+                    hook,
+                    nonce,
+                });
+            }
+        }
+
+        /// Pull `fee` from `from` to the fee recipient via `transfer_from` on `fee_token`
+        fn pull_fee(&self, fee_token: AccountId, from: AccountId, fee: Balance) -> Result<()> {
+            use ink::env::call::{build_call, ExecutionInput, Selector};
+            let result = build_call::<ink::env::DefaultEnvironment>()
+                .call(fee_token)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("transfer_from")))
+                        .push_arg(from)
+                        .push_arg(self.fee_recipient)
+                        .push_arg(fee),
+                )
+                .returns::<Result<()>>()
+                .try_invoke();
+            match result {
+                Ok(Ok(Ok(()))) => Ok(()),
+                _ => Err(Error::FeeTokenTransferFailed),
+            }
+        }
+
+        /// Mark `nonce_hash` used and canceled for good on behalf of `from`
+        fn cancel_nonce(
+            &mut self,
+            from: AccountId,
+            nonce_hash: [u8; 32],
+            nonce: String,
+        ) -> Result<()> {
+            if self.used_nonces.contains(nonce_hash) {
+                return Err(Error::NonceAlreadyUsed);
+            }
+            self.used_nonces.insert(nonce_hash, &u64::MAX);
+            self.canceled_nonces.insert(nonce_hash, &());
+            self.partial_remaining.remove(nonce_hash);
+            self.partial_valid_until.remove(nonce_hash);
+            self.env().emit_event(AuthorizationCanceled {
+                from,
+                nonce_hash,
+                nonce,
+            });
+            Ok(())
+        }
+
+        /// Remove `from`'s expired nonce or partial draw record under `nonce_hash`
+        /// and raise its `pruned_through` watermark
+        fn prune_nonce(&mut self, from: AccountId, nonce_hash: [u8; 32]) -> Result<()> {
+            let valid_until = match self.used_nonces.get(nonce_hash) {
+                Some(valid_until) => valid_until,
+                None => self.partial_valid_until.get(nonce_hash).ok_or(Error::NonceNotFound)?,
+            };
+            if valid_until == 0 || !self.is_past_grace(valid_until) {
+                return Err(Error::NonceNotExpired);
+            }
+            self.used_nonces.remove(nonce_hash);
+            self.partial_remaining.remove(nonce_hash);
+            self.partial_valid_until.remove(nonce_hash);
+            if valid_until > self.pruned_through(from) {
+                self.pruned_through.insert(from, &valid_until);
+            }
+            Ok(())
+        }
+
+        /// Reject empty nonces and nonces longer than MAX_NONCE_LEN
+        fn validate_nonce(nonce: &str) -> Result<()> {
+            if nonce.is_empty() || nonce.len() > MAX_NONCE_LEN {
+                return Err(Error::InvalidNonce);
+            }
+            Ok(())
+        }
+
+        /// Storage key for a relayer's idempotency key
+        fn idempotency_key_hash(&self, key: &str) -> [u8; 32] {
+            let mut data = Vec::new();
+            data.extend_from_slice(self.env().caller().as_ref());
+            data.extend_from_slice(key.as_bytes());
+
+            let mut output = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&data, &mut output);
+            output
+        }
+
+        /// Current spend-limit day bucket
+        fn current_day(&self) -> u64 {
+            self.env().block_timestamp() / DAY_MS
+        }
+
+        /// Return the payer's spend for today including `amount`, or
+        /// `SpendLimitExceeded` if that would exceed the daily limit
+        fn check_spend_limit(&self, from: AccountId, amount: Balance) -> Result<Balance> {
+            let spent = self
+                .spent_today(from)
+                .checked_add(amount)
+                .ok_or(Error::SpendLimitExceeded)?;
+            if let Some(limit) = self.spend_limit_per_day {
+                if spent > limit {
+                    return Err(Error::SpendLimitExceeded);
+                }
+            }
+            Ok(spent)
+        }
+
+        /// Refundable amount left for `recipient` after refunding `amount` of the
+        /// payment settled under `nonce_hash`
+        fn remaining_refundable(
+            &self,
+            nonce_hash: [u8; 32],
+            recipient: AccountId,
+            amount: Balance,
+        ) -> Result<Balance> {
+            if !self.used_nonces.contains(nonce_hash)
+                && !self.partial_remaining.contains(nonce_hash)
+            {
+                return Err(Error::NonceNotFound);
+            }
+            let Some(refundable) = self.refundable.get((nonce_hash, recipient)) else {
+                return Err(Error::CallerNotRecipient);
+            };
+            refundable.checked_sub(amount).ok_or(Error::RefundExceedsPayment)
+        }
+
+        /// Reject settlements while the contract is paused
+        fn when_not_paused(&self) -> Result<()> {
+            if self.paused {
+                return Err(Error::ContractPaused);
+            }
+            Ok(())
+        }
+
+        /// Check whether an authorization valid until `valid_until` has expired,
+        /// allowing `expiry_grace_ms` of clock skew
+        fn is_expired(&self, valid_until: u64) -> bool {
+            if self.allow_no_expiry && valid_until == 0 {
+                return false;
+            }
+            self.env().block_timestamp() > valid_until.saturating_add(self.expiry_grace_ms)
+        }
+
+        /// Check whether an authorization is expired beyond the late grace period
+        fn is_past_grace(&self, valid_until: u64) -> bool {
+            if !self.is_expired(valid_until) {
+                return false;
+            }
+            let deadline = valid_until
+                .saturating_add(self.expiry_grace_ms)
+                .saturating_add(self.allow_late_within_ms);
+            self.env().block_timestamp() > deadline
+        }
+
+        /// Reject authorizations past the grace period, at or below the payer's
+        /// prune watermark, or valid for too long; return how late it is, if at all
+        fn check_expiry(&self, from: AccountId, valid_until: u64) -> Result<Option<u64>> {
+            if self.is_past_grace(valid_until) {
+                return Err(Error::PaymentExpired);
+            }
+            if valid_until != 0 && valid_until <= self.pruned_through(from) {
+                return Err(Error::PaymentExpired);
+            }
+            if let Some(window) = self.max_validity_window_ms {
+                let never_expires = self.allow_no_expiry && valid_until == 0;
+                let remaining = valid_until.saturating_sub(self.env().block_timestamp());
+                if never_expires || remaining > window {
+                    return Err(Error::ValidityTooLong);
+                }
+            }
+            if self.is_expired(valid_until) {
+                return Ok(Some(self.env().block_timestamp() - valid_until));
+            }
+            Ok(None)
+        }
+
+        /// Emit ConfigChanged for `setting`, attributed to the caller
+        fn emit_config_changed(&self, setting: &str) {
+            self.env().emit_event(ConfigChanged {
+                changed_by: self.env().caller(),
+                setting: String::from(setting),
+            });
+        }
+
+        /// Emit the current token metadata
+        fn emit_metadata_changed(&self) {
+            self.env().emit_event(MetadataChanged {
+                name: self.token_name.clone(),
+                symbol: self.token_symbol.clone(),
+            });
+        }
+
+        /// Write a balance, keeping `unique_holders` in sync when it crosses zero
+        fn set_balance(&mut self, account: AccountId, balance: Balance) {
+            let previous = self.balance_of(account);
+            if previous == 0 && balance > 0 {
+                self.unique_holders = self.unique_holders.saturating_add(1);
+            } else if previous > 0 && balance == 0 {
+                self.unique_holders = self.unique_holders.saturating_sub(1);
+            }
+            self.balances.insert(account, &balance);
+            if self.checkpoints_enabled {
+                self.write_checkpoint(account, balance);
+            }
+        }
+
+        /// Record `balance` for `account` at the current block
+        ///
+        /// Overwrites the account's last checkpoint if it was taken in the same
+        /// block, so each account has at most one checkpoint per block.
+        fn write_checkpoint(&mut self, account: AccountId, balance: Balance) {
+            let block_number = self.env().block_number();
+            let count = self.checkpoint_counts.get(account).unwrap_or(0);
+            let same_block = count > 0
+                && self
+                    .balance_checkpoints
+                    .get((account, count - 1))
+                    .is_some_and(|(block, _)| block == block_number);
+            let index = if same_block { count - 1 } else { count };
+            self.balance_checkpoints.insert((account, index), &(block_number, balance));
+            if !same_block {
+                self.checkpoint_counts.insert(account, &count.saturating_add(1));
+            }
+        }
+
+        /// Internal transfer helper
+        fn transfer_from_to(&mut self, from: AccountId, to: AccountId, value: Balance) -> Result<()> {
+            let from_balance = self.balance_of(from);
+            if from_balance < value {
+                return Err(Error::PSP22(PSP22Error::InsufficientBalance));
+            }
+
+            let new_from_balance = from_balance.checked_sub(value)
+                .ok_or(Error::PSP22(PSP22Error::InsufficientBalance))?;
+
+            // Check the credit before debiting, so an overflow changes nothing
+            let to_balance = if from == to { new_from_balance } else { self.balance_of(to) };
+            let new_to_balance = to_balance.checked_add(value)
+                .ok_or(Error::PSP22(PSP22Error::Overflow))?;
+            self.set_balance(from, new_from_balance);
+            self.set_balance(to, new_to_balance);
+
+            self.env().emit_event(Transfer {
+                from: Some(from),
+                to: Some(to),
+                value,
+            });
+
+            Ok(())
+        }
+
+        /// Compute a unique hash for the nonce: Blake2x256(from ++ nonce bytes)
+        ///
+        /// This is the storage key of `used_nonces`; changing the algorithm or
+        /// layout orphans every nonce already recorded.
+        fn compute_nonce_hash(&self, from: &AccountId, nonce: &str) -> [u8; 32] {
+            let mut data = Vec::new();
+            data.extend_from_slice(from.as_ref());
+            data.extend_from_slice(nonce.as_bytes());
+
+            let mut output = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&data, &mut output);
+            output
+        }
+
+        /// Nonce hash of a bytes32 nonce: Blake2x256(B32_NONCE_TAG ++ from ++ nonce)
+        ///
+        /// The leading tag keeps bytes32 nonces apart from String nonces with
+        /// the same bytes.
+        fn compute_nonce_hash_b32(&self, from: &AccountId, nonce: &[u8; 32]) -> [u8; 32] {
+            let mut data = B32_NONCE_TAG.to_vec();
+            data.extend_from_slice(from.as_ref());
+            data.extend_from_slice(nonce);
+
+            let mut output = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&data, &mut output);
+            output
+        }
+
+        /// Nonce hash of an approval nonce:
+        /// Blake2x256(APPROVE_MESSAGE_PREFIX ++ owner ++ nonce bytes)
+        fn compute_nonce_hash_approval(&self, owner: &AccountId, nonce: &str) -> [u8; 32] {
+            let mut data = APPROVE_MESSAGE_PREFIX.to_vec();
+            data.extend_from_slice(owner.as_ref());
+            data.extend_from_slice(nonce.as_bytes());
+
+            let mut output = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&data, &mut output);
+            output
+        }
+
+        /// Nonce hash of a recipient-scoped nonce:
+        /// Blake2x256(SCOPED_NONCE_TAG ++ from ++ to ++ nonce bytes)
+        fn compute_nonce_hash_scoped(
+            &self,
+            from: &AccountId,
+            to: &AccountId,
+            nonce: &str,
+        ) -> [u8; 32] {
+            let mut data = SCOPED_NONCE_TAG.to_vec();
+            data.extend_from_slice(from.as_ref());
+            data.extend_from_slice(to.as_ref());
+            data.extend_from_slice(nonce.as_bytes());
+
+            let mut output = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&data, &mut output);
+            output
+        }
+
+        /// Blake2x256 of the SCALE-encoded split recipients, signed in place of `to`
+        fn hash_recipients(recipients: &[(AccountId, Balance)]) -> AccountId {
+            use scale::Encode;
+            let mut output = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&recipients.encode(), &mut output);
+            AccountId::from(output)
+        }
+
+        /// Lowercase `0x`-prefixed hex of `bytes`, used to report bytes32 nonces
+        fn hex_encode(bytes: &[u8]) -> String {
+            const DIGITS: &[u8; 16] = b"0123456789abcdef";
+            let mut out = String::with_capacity(2 + bytes.len() * 2);
+            out.push_str("0x");
+            for byte in bytes {
+                out.push(DIGITS[(byte >> 4) as usize] as char);
+                out.push(DIGITS[(byte & 0x0f) as usize] as char);
+            }
+            out
+        }
+
+        /// Compute the domain separator for a contract instance
+        ///
+        /// `Blake2x256(DOMAIN_PERSONALIZATION ++ DOMAIN_VERSION (1) ++ chain_id (u32 LE, 4)
+        ///  ++ contract account id (32))`
+        fn compute_domain_separator(chain_id: u32, contract: AccountId) -> [u8; 32] {
+            use scale::Encode;
+            let mut data = Vec::new();
+            data.extend_from_slice(DOMAIN_PERSONALIZATION);
+            data.push(DOMAIN_VERSION);
+            data.extend_from_slice(&chain_id.encode());
+            data.extend_from_slice(&contract.encode());
+
+            let mut output = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&data, &mut output);
+            output
+        }
+
+        /// Build the message an authorization signs
+        ///
+        /// Byte layout (SCALE encodings, integers little-endian):
+        /// `from (32) ++ to (32) ++ amount (u128, 16) ++ nonce (raw UTF-8 bytes)
+        ///  ++ valid_until (u64, 8) ++ domain_separator (32) [++ epoch (u32, 4)]`
+        ///
+        /// The domain separator binds a signature to this contract instance on
+        /// this chain. The payer's `authorization_epoch` is appended only when it
+        /// is nonzero.
+        fn payment_message(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            nonce: &str,
+            valid_until: u64,
+        ) -> Vec<u8> {
+            self.payment_message_bytes(from, to, amount, nonce.as_bytes(), valid_until)
+        }
+
+        /// `payment_message` over raw nonce bytes
+        fn payment_message_bytes(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            nonce: &[u8],
+            valid_until: u64,
+        ) -> Vec<u8> {
+            use scale::Encode;
+            let mut message = Vec::new();
+            message.extend_from_slice(&from.encode());
+            message.extend_from_slice(&to.encode());
+            message.extend_from_slice(&amount.encode());
+            message.extend_from_slice(nonce);
+            message.extend_from_slice(&valid_until.encode());
+            message.extend_from_slice(&self.domain_separator);
+            let epoch = self.authorization_epoch(from);
+            if epoch > 0 {
+                message.extend_from_slice(&epoch.encode());
+            }
+            message
+        }
+
+        /// Build the message signed for `transfer_with_authorization_v2`
+        ///
+        /// `PAYMENT_V2_PREFIX ++ payment_message ++ issued_at (u64 LE)`. The
+        /// prefix keeps v1 and v2 signatures from verifying as each other.
+        fn payment_message_v2(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            nonce: &str,
+            valid_until: u64,
+            issued_at: u64,
+        ) -> Vec<u8> {
+            use scale::Encode;
+            let mut message = PAYMENT_V2_PREFIX.to_vec();
+            message.extend_from_slice(&self.payment_message(from, to, amount, nonce, valid_until));
+            message.extend_from_slice(&issued_at.encode());
+            message
+        }
+
+        /// Verify that `signer` signed `message` under the given scheme
+        ///
+        /// * `Sr25519` - the 64-byte signature must verify against `signer` as
+        ///   the public key over the Blake2x256 hash of the message
+        /// * `Ecdsa` - the 65-byte (r, s, v) signature is made over the Keccak256
+        ///   hash of the message; the recovered compressed public key must
+        ///   Blake2x256-hash to `signer`
+        ///
+        /// Malformed signatures are rejected by `is_well_formed` before any
+        /// hashing or host verification. Replay protection never relies on
+        /// signature bytes being unique: nonces are keyed by payer and nonce,
+        /// so an alternative encoding of a valid signature cannot settle the
+        /// same payment twice.
+        fn verify_signed_message(
+            &self,
+            signer: AccountId,
+            message: &[u8],
+            signature: &[u8],
+            scheme: SignatureScheme,
+        ) -> bool {
+            let is_valid = self.is_signature_valid(signer, message, signature, scheme);
+
+            // Malformed signatures are recorded too, so they show up when debugging
+            #[cfg(feature = "debug-events")]
+            #[allow(clippy::cast_possible_truncation)]
+            self.env().emit_event(DebugSignature {
+                message_hash: Self::hash_message(&self.frame_message(message), scheme),
+                signature_valid: is_valid,
+                signature_len: signature.len() as u32,
+            });
+
+            is_valid
+        }
+
+        /// `verify_signed_message` without the `DebugSignature` event, for views
+        fn is_signature_valid(
+            &self,
+            signer: AccountId,
+            message: &[u8],
+            signature: &[u8],
+            scheme: SignatureScheme,
+        ) -> bool {
+            if !Self::is_well_formed(signature, scheme) {
+                return false;
+            }
+            // Hash the framed message and verify with the selected scheme
+            let hash = Self::hash_message(&self.frame_message(message), scheme);
+            match scheme {
+                SignatureScheme::Sr25519 => Self::verify_sr25519(&hash, signature, &signer),
+                SignatureScheme::Ecdsa => Self::verify_ecdsa(&hash, signature, &signer),
+            }
+        }
+
+        /// Build the message a merchant signs to authorize a refund
+        ///
+        /// `REFUND_MESSAGE_PREFIX ++ merchant ++ to ++ amount (u128 LE) ++
+        /// original_nonce bytes ++ domain_separator`. The prefix keeps refund
+        /// signatures from ever verifying as payment authorizations.
+        fn refund_message(
+            &self,
+            merchant: AccountId,
+            to: AccountId,
+            amount: Balance,
+            original_nonce: &str,
+        ) -> Vec<u8> {
+            use scale::Encode;
+            let mut message = REFUND_MESSAGE_PREFIX.to_vec();
+            message.extend_from_slice(&merchant.encode());
+            message.extend_from_slice(&to.encode());
+            message.extend_from_slice(&amount.encode());
+            message.extend_from_slice(original_nonce.as_bytes());
+            message.extend_from_slice(&self.domain_separator);
+            message
+        }
+
+        /// Apply the configured signing format to a message
+        fn frame_message(&self, message: &[u8]) -> Vec<u8> {
+            match self.signing_format {
+                SigningFormat::Raw => message.to_vec(),
+                SigningFormat::WrappedBytes => {
+                    let mut framed = b"<Bytes>".to_vec();
+                    framed.extend_from_slice(message);
+                    framed.extend_from_slice(b"</Bytes>");
+                    framed
+                }
+            }
+        }
+
+        /// Digest signed under a scheme: Blake2x256 for sr25519, Keccak256 for ECDSA
+        fn hash_message(message: &[u8], scheme: SignatureScheme) -> [u8; 32] {
+            let mut hash = [0u8; 32];
+            match scheme {
+                SignatureScheme::Sr25519 => {
+                    ink::env::hash_bytes::<ink::env::hash::Blake2x256>(message, &mut hash)
+                }
+                SignatureScheme::Ecdsa => {
+                    ink::env::hash_bytes::<ink::env::hash::Keccak256>(message, &mut hash)
+                }
+            }
+            hash
+        }
+
+        /// Cheap shape checks run before signature verification
+        ///
+        /// Signatures must have the scheme's exact length and must not be all
+        /// zero. sr25519 signatures must also carry the schnorrkel marker bit
+        /// and a canonical scalar `s` (below the group order), which rules out
+        /// the `s + order` malleated form.
+        fn is_well_formed(signature: &[u8], scheme: SignatureScheme) -> bool {
+            let expected_len = match scheme {
+                SignatureScheme::Sr25519 => 64,
+                SignatureScheme::Ecdsa => 65,
+            };
+            if signature.len() != expected_len || signature.iter().all(|byte| *byte == 0) {
+                return false;
+            }
+            if scheme == SignatureScheme::Sr25519 {
+                if signature[63] & 0x80 == 0 {
+                    return false;
+                }
+                let mut scalar = [0u8; 32];
+                scalar.copy_from_slice(&signature[32..]);
+                scalar[31] &= 0x7f;
+                // Compare little-endian, most significant byte first
+                return scalar.iter().rev().lt(RISTRETTO_GROUP_ORDER.iter().rev());
+            }
+            true
+        }
+
+        /// Verify a 64-byte sr25519 signature with `from` as the public key
+        fn verify_sr25519(hash: &[u8; 32], signature: &[u8], from: &AccountId) -> bool {
+            let sig_array: [u8; 64] = match signature.try_into() {
+                Ok(sig) => sig,
+                Err(_) => return false,
+            };
+            let pub_key: &[u8; 32] = from.as_ref();
+            ink::env::sr25519_verify(&sig_array, hash, pub_key).is_ok()
+        }
+
+        /// Recover the signer of a 65-byte ECDSA signature and compare it to `from`
+        fn verify_ecdsa(hash: &[u8; 32], signature: &[u8], from: &AccountId) -> bool {
+            let sig_array: [u8; 65] = match signature.try_into() {
+                Ok(sig) => sig,
+                Err(_) => return false,
+            };
+            let mut pub_key = [0u8; 33];
+            if ink::env::ecdsa_recover(&sig_array, hash, &mut pub_key).is_err() {
+                return false;
+            }
+
+            // Substrate derives ECDSA account ids as the Blake2x256 of the compressed key
+            let mut signer = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&pub_key, &mut signer);
+            AccountId::from(signer) == *from
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[ink::test]
+        fn new_works() {
+            let initial_supply = 1_000_000_000_000; // 1 trillion
+            let contract = Httpusd::new(initial_supply, 100).unwrap(); // 1% fee
+            assert_eq!(contract.total_supply(), initial_supply);
+            assert_eq!(contract.get_facilitator_fee(), 100);
+        }
+
+        fn events_of<E: ink::env::Event + scale::Decode>() -> Vec<E> {
+            let signature_topic = E::SIGNATURE_TOPIC.unwrap().to_vec();
+            ink::env::test::recorded_events()
+                .filter(|event| event.topics.first() == Some(&signature_topic))
+                .map(|event| E::decode(&mut &event.data[..]).unwrap())
+                .collect()
+        }
+
+        fn decode_transfer(event: &ink::env::test::EmittedEvent) -> Transfer {
+            <Transfer as scale::Decode>::decode(&mut &event.data[..]).unwrap()
+        }
+
+        #[ink::test]
+        fn initial_mint_emits_transfer_without_sender() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let _contract = Httpusd::new(1_000, 100).unwrap();
+
+            let events: Vec<_> = ink::env::test::recorded_events().collect();
+            assert_eq!(events.len(), 1);
+            let transfer = decode_transfer(&events[0]);
+            assert_eq!(transfer.from, None);
+            assert_eq!(transfer.to, Some(accounts.alice));
+            assert_eq!(transfer.value, 1_000);
+        }
+
+        #[ink::test]
+        fn transfer_emits_transfer_with_both_parties() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 100).unwrap();
+            assert_eq!(contract.transfer(accounts.bob, 10), Ok(()));
+
+            let last = ink::env::test::recorded_events().last().unwrap();
+            let transfer = decode_transfer(&last);
+            assert_eq!(transfer.from, Some(accounts.alice));
+            assert_eq!(transfer.to, Some(accounts.bob));
+            assert_eq!(transfer.value, 10);
+        }
+
+        #[ink::test]
+        fn mint_and_burn_keep_supply_consistent() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 100).unwrap();
+
+            assert_eq!(contract.mint(accounts.bob, 500), Ok(()));
+            assert_eq!(contract.total_supply(), 1_500);
+            assert_eq!(contract.balance_of(accounts.bob), 500);
+
+            assert_eq!(contract.burn(accounts.bob, 200), Ok(()));
+            assert_eq!(contract.burn(accounts.alice, 100), Ok(()));
+            assert_eq!(contract.total_supply(), 1_200);
+            assert_eq!(
+                contract.balance_of(accounts.alice) + contract.balance_of(accounts.bob),
+                contract.total_supply()
+            );
+
+            assert_eq!(
+                contract.burn(accounts.bob, 301),
+                Err(Error::PSP22(PSP22Error::InsufficientBalance))
+            );
+            assert_eq!(contract.total_supply(), 1_200);
+        }
+
+        #[ink::test]
+        fn mint_respects_max_supply() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new_with_max_supply(600, 0, Some(1_000)).unwrap();
+            assert_eq!(contract.max_supply(), Some(1_000));
+
+            assert_eq!(contract.mint(accounts.bob, 400), Ok(()));
+            assert_eq!(contract.total_supply(), 1_000);
+            assert_eq!(contract.mint(accounts.bob, 1), Err(Error::MaxSupplyExceeded));
+            assert_eq!(contract.total_supply(), 1_000);
+
+            // Burning frees room under the cap
+            assert_eq!(contract.burn(accounts.bob, 100), Ok(()));
+            assert_eq!(contract.mint(accounts.bob, 100), Ok(()));
+        }
+
+        #[ink::test]
+        fn max_supply_rejects_initial_supply_above_cap() {
+            assert_eq!(
+                Httpusd::new_with_max_supply(1_001, 0, Some(1_000)).err(),
+                Some(Error::MaxSupplyExceeded)
+            );
+            let contract = Httpusd::new_with_max_supply(1_001, 0, None).unwrap();
+            assert_eq!(contract.max_supply(), None);
+        }
+
+        #[ink::test]
+        fn mint_and_burn_emit_transfer_with_none() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 100).unwrap();
+
+            assert_eq!(contract.mint(accounts.bob, 500), Ok(()));
+            let minted = decode_transfer(&ink::env::test::recorded_events().last().unwrap());
+            assert_eq!(minted.from, None);
+            assert_eq!(minted.to, Some(accounts.bob));
+
+            assert_eq!(contract.burn(accounts.bob, 200), Ok(()));
+            let burned = decode_transfer(&ink::env::test::recorded_events().last().unwrap());
+            assert_eq!(burned.from, Some(accounts.bob));
+            assert_eq!(burned.to, None);
+            assert_eq!(burned.value, 200);
+        }
+
+        #[ink::test]
+        fn only_owner_can_mint_and_burn() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 100).unwrap();
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let not_owner = Err(Error::NotOwner);
+            assert_eq!(contract.mint(accounts.bob, 500), not_owner);
+            assert_eq!(contract.burn(accounts.alice, 500), not_owner);
+            assert_eq!(contract.total_supply(), 1_000);
+        }
+
+        #[ink::test]
+        fn unique_holders_tracks_nonzero_balances() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 100).unwrap();
+            assert_eq!(contract.unique_holders(), 1);
+
+            assert_eq!(contract.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(contract.transfer(accounts.charlie, 100), Ok(()));
+            assert_eq!(contract.unique_holders(), 3);
+
+            // Crediting an existing holder again does not count twice
+            assert_eq!(contract.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(contract.unique_holders(), 3);
+
+            // Draining accounts to zero decrements
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.transfer(accounts.alice, 200), Ok(()));
+            assert_eq!(contract.unique_holders(), 2);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(contract.burn(accounts.charlie, 100), Ok(()));
+            assert_eq!(contract.unique_holders(), 1);
+
+            assert_eq!(contract.mint(accounts.django, 1), Ok(()));
+            assert_eq!(contract.unique_holders(), 2);
+        }
+
+        #[ink::test]
+        fn increase_allowance_accumulates() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+
+            assert_eq!(contract.increase_allowance(accounts.bob, 100), Ok(()));
+            assert_eq!(contract.allowance(accounts.alice, accounts.bob), 100);
+            assert_eq!(contract.increase_allowance(accounts.bob, 50), Ok(()));
+            assert_eq!(contract.increase_allowance(accounts.bob, 25), Ok(()));
+            assert_eq!(contract.allowance(accounts.alice, accounts.bob), 175);
+
+            let last = ink::env::test::recorded_events().last().unwrap();
+            let approval = <Approval as scale::Decode>::decode(&mut &last.data[..]).unwrap();
+            assert_eq!(approval.value, 175);
+        }
+
+        #[ink::test]
+        fn decrease_allowance_below_zero_fails() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+
+            assert_eq!(contract.approve(accounts.bob, 100), Ok(()));
+            assert_eq!(contract.decrease_allowance(accounts.bob, 40), Ok(()));
+            assert_eq!(contract.allowance(accounts.alice, accounts.bob), 60);
+            assert_eq!(
+                contract.decrease_allowance(accounts.bob, 61),
+                Err(Error::PSP22(PSP22Error::InsufficientAllowance))
+            );
+            assert_eq!(contract.allowance(accounts.alice, accounts.bob), 60);
+        }
+
+        #[ink::test]
+        fn fee_cap_is_enforced() {
+            let mut contract = Httpusd::new_with_max_fee(1_000, 100, 1_000).unwrap();
+            assert_eq!(contract.get_max_fee_bps(), 1_000);
+
+            assert_eq!(contract.set_facilitator_fee(10_001), Err(Error::FeeTooHigh));
+            assert_eq!(contract.set_facilitator_fee(1_001), Err(Error::FeeTooHigh));
+            assert_eq!(contract.get_facilitator_fee(), 100);
+
+            assert_eq!(contract.set_facilitator_fee(900), Ok(()));
+            assert_eq!(contract.get_facilitator_fee(), 900);
+        }
+
+        #[ink::test]
+        fn constructors_reject_fee_above_cap() {
+            assert_eq!(Httpusd::new(1_000, DEFAULT_MAX_FEE_BPS + 1).err(), Some(Error::FeeTooHigh));
+            assert_eq!(Httpusd::new_with_max_fee(1_000, 600, 500).err(), Some(Error::FeeTooHigh));
+            assert_eq!(Httpusd::new_with_max_fee(1_000, 0, 10_001).err(), Some(Error::FeeTooHigh));
+            assert!(Httpusd::new_with_max_fee(1_000, 2_000, 5_000).is_ok());
+        }
+
+        #[ink::test]
         fn nonce_tracking_works() {
             let initial_supply = 1_000_000_000_000;
-            let mut contract = Httpusd::new(initial_supply, 100);
+            let mut contract = Httpusd::new(initial_supply, 100).unwrap();
             let account = AccountId::from([0x02; 32]);
             let nonce = String::from("test-nonce-123");
 
-            assert!(!contract.is_nonce_used(account, nonce.clone()));
+            assert!(!contract.is_nonce_used(account, nonce.clone()));
+
+            let nonce_hash = contract.compute_nonce_hash(&account, &nonce);
+            contract.used_nonces.insert(nonce_hash, &1_000);
+
+            assert!(contract.is_nonce_used(account, nonce));
+        }
+
+        #[ink::test]
+        fn expired_nonce_can_be_pruned() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_payment(&payer, to, 10_000, "prune-me", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    1_000,
+                    String::from("prune-me"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            let nonce_hash = contract.compute_nonce_hash(&from, &String::from("prune-me"));
+            assert_eq!(contract.used_nonces.get(nonce_hash), Some(1_000));
+
+            // Still valid: cannot prune yet
+            assert_eq!(
+                contract.prune_expired_nonce(from, String::from("prune-me")),
+                Err(Error::NonceNotExpired)
+            );
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_001);
+            assert_eq!(contract.prune_expired_nonce(from, String::from("prune-me")), Ok(()));
+            assert!(!contract.is_nonce_used(from, String::from("prune-me")));
+            assert_eq!(contract.used_nonces.get(nonce_hash), None);
+            assert_eq!(
+                contract.prune_expired_nonce(from, String::from("prune-me")),
+                Err(Error::NonceNotFound)
+            );
+        }
+
+        #[ink::test]
+        fn pruned_authorization_stays_dead_after_grace_is_widened() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let settle = |contract: &mut Httpusd, nonce: &str, valid_until: u64| {
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    valid_until,
+                    String::from(nonce),
+                    sign_payment(&payer, to, 1_000, nonce, valid_until),
+                    SignatureScheme::Sr25519,
+                )
+            };
+            assert_eq!(settle(&mut contract, "replay", 1_000), Ok(()));
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_500);
+            assert_eq!(contract.prune_expired_nonce(from, String::from("replay")), Ok(()));
+            assert_eq!(contract.pruned_through(from), 1_000);
+
+            // Widening the grace would otherwise make the public signature valid again
+            assert_eq!(contract.set_expiry_grace(10_000), Ok(()));
+            assert_eq!(settle(&mut contract, "replay", 1_000), Err(Error::PaymentExpired));
+            assert_eq!(contract.balance_of(to), 1_000);
+
+            // Later authorizations from the payer are unaffected
+            assert_eq!(settle(&mut contract, "fresh", 1_001), Ok(()));
+        }
+
+        #[ink::test]
+        fn scoped_b32_approval_and_partial_nonces_can_be_pruned() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            assert_eq!(
+                contract.transfer_with_authorization_scoped(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("scoped"),
+                    sign_payment_scoped(&payer, to, 1_000, "scoped", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                contract.transfer_with_authorization_b32(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    [0x42; 32],
+                    sign_payment_b32(&payer, to, 1_000, [0x42; 32], 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                contract.approve_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("approve"),
+                    sign_approval(&payer, to, 1_000, "approve", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            let draw = |contract: &mut Httpusd| {
+                contract.transfer_with_authorization_partial(
+                    from,
+                    to,
+                    100,
+                    30,
+                    1_000,
+                    String::from("partial"),
+                    sign_partial(&payer, to, 100, "partial", 1_000),
+                    SignatureScheme::Sr25519,
+                )
+            };
+            assert_eq!(draw(&mut contract), Ok(()));
+            assert_eq!(
+                contract.prune_expired_nonce(from, String::from("partial")),
+                Err(Error::NonceNotExpired)
+            );
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_001);
+            assert_eq!(
+                contract.prune_expired_nonce_scoped(from, to, String::from("scoped")),
+                Ok(())
+            );
+            assert_eq!(contract.prune_expired_nonce_b32(from, [0x42; 32]), Ok(()));
+            assert_eq!(contract.prune_expired_approval(from, String::from("approve")), Ok(()));
+            assert_eq!(contract.prune_expired_nonce(from, String::from("partial")), Ok(()));
+            assert_eq!(contract.partial_remaining(from, String::from("partial")), None);
+            for nonce_hash in [
+                contract.compute_nonce_hash_scoped(&from, &to, "scoped"),
+                contract.compute_nonce_hash_b32(&from, &[0x42; 32]),
+                contract.compute_nonce_hash_approval(&from, "approve"),
+                contract.compute_nonce_hash(&from, "partial"),
+            ] {
+                assert_eq!(contract.used_nonces.get(nonce_hash), None);
+                assert_eq!(contract.partial_valid_until.get(nonce_hash), None);
+            }
+            assert_eq!(
+                contract.prune_expired_nonce_b32(from, [0x42; 32]),
+                Err(Error::NonceNotFound)
+            );
+
+            // The pruned partial authorization cannot be drawn afresh
+            assert_eq!(contract.set_expiry_grace(10_000), Ok(()));
+            assert_eq!(draw(&mut contract), Err(Error::PaymentExpired));
+        }
+
+        #[ink::test]
+        fn zero_expiry_nonce_is_never_prunable() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.set_allow_no_expiry(true), Ok(()));
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    0,
+                    String::from("forever"),
+                    sign_payment(&payer, to, 1_000, "forever", 0),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+
+            assert_eq!(contract.set_allow_no_expiry(false), Ok(()));
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert_eq!(
+                contract.prune_expired_nonce(from, String::from("forever")),
+                Err(Error::NonceNotExpired)
+            );
+        }
+
+        fn authorization(from: AccountId, nonce: &str) -> Authorization {
+            Authorization {
+                from,
+                to: AccountId::from([0x03; 32]),
+                amount: 1_000,
+                valid_until: 0,
+                nonce: String::from(nonce),
+                signature: Vec::new(),
+                scheme: SignatureScheme::Sr25519,
+            }
+        }
+
+        fn payment_message(
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            nonce: &str,
+            valid_until: u64,
+        ) -> Vec<u8> {
+            payment_message_for_chain(from, to, amount, nonce, valid_until, 0)
+        }
+
+        fn payment_message_for_chain(
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            nonce: &str,
+            valid_until: u64,
+            chain_id: u32,
+        ) -> Vec<u8> {
+            use scale::Encode;
+            let mut message = Vec::new();
+            message.extend_from_slice(&from.encode());
+            message.extend_from_slice(&to.encode());
+            message.extend_from_slice(&amount.encode());
+            message.extend_from_slice(nonce.as_bytes());
+            message.extend_from_slice(&valid_until.encode());
+            message.extend_from_slice(&expected_domain_separator(chain_id));
+            message
+        }
+
+        /// Domain separator recomputed independently for the current callee
+        fn expected_domain_separator(chain_id: u32) -> [u8; 32] {
+            use scale::Encode;
+            let contract = ink::env::test::callee::<ink::env::DefaultEnvironment>();
+            let mut data = b"polkax402:httpusd".to_vec();
+            data.push(1);
+            data.extend_from_slice(&chain_id.encode());
+            data.extend_from_slice(&contract.encode());
+            let mut output = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&data, &mut output);
+            output
+        }
+
+        fn sr25519_keypair(seed: u8) -> schnorrkel::Keypair {
+            schnorrkel::MiniSecretKey::from_bytes(&[seed; 32])
+                .unwrap()
+                .expand_to_keypair(schnorrkel::ExpansionMode::Ed25519)
+        }
+
+        fn sr25519_account(keypair: &schnorrkel::Keypair) -> AccountId {
+            AccountId::from(keypair.public.to_bytes())
+        }
+
+        fn sign_sr25519(keypair: &schnorrkel::Keypair, message: &[u8]) -> Vec<u8> {
+            let mut hash = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(message, &mut hash);
+            let context = schnorrkel::signing_context(b"substrate");
+            keypair.sign(context.bytes(&hash)).to_bytes().to_vec()
+        }
+
+        fn ecdsa_account(seed: u8) -> AccountId {
+            let secp = secp256k1::Secp256k1::new();
+            let secret = secp256k1::SecretKey::from_slice(&[seed; 32]).unwrap();
+            let public = secp256k1::PublicKey::from_secret_key(&secp, &secret).serialize();
+            let mut account = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&public, &mut account);
+            AccountId::from(account)
+        }
+
+        fn sign_ecdsa(seed: u8, message: &[u8]) -> Vec<u8> {
+            let mut hash = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Keccak256>(message, &mut hash);
+            let secp = secp256k1::Secp256k1::new();
+            let secret = secp256k1::SecretKey::from_slice(&[seed; 32]).unwrap();
+            let digest = secp256k1::Message::from_digest_slice(&hash).unwrap();
+            let (recovery_id, compact) =
+                secp.sign_ecdsa_recoverable(&digest, &secret).serialize_compact();
+            let mut signature = compact.to_vec();
+            signature.push(recovery_id.to_i32() as u8);
+            signature
+        }
+
+        fn sign_payment(
+            payer: &schnorrkel::Keypair,
+            to: AccountId,
+            amount: Balance,
+            nonce: &str,
+            valid_until: u64,
+        ) -> Vec<u8> {
+            let from = sr25519_account(payer);
+            sign_sr25519(payer, &payment_message(from, to, amount, nonce, valid_until))
+        }
+
+        #[ink::test]
+        fn sr25519_payment_works() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let message = payment_message(from, to, 10_000, "sr-nonce", 1_000);
+            let signature = sign_sr25519(&payer, &message);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    1_000,
+                    String::from("sr-nonce"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(contract.balance_of(to), 9_900);
+            assert_eq!(contract.balance_of(from), 90_000);
+        }
+
+        #[ink::test]
+        fn ecdsa_payment_works() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let from = ecdsa_account(0x22);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let message = payment_message(from, to, 10_000, "ecdsa-nonce", 1_000);
+            let signature = sign_ecdsa(0x22, &message);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    1_000,
+                    String::from("ecdsa-nonce"),
+                    signature,
+                    SignatureScheme::Ecdsa,
+                ),
+                Ok(())
+            );
+            assert_eq!(contract.balance_of(to), 9_900);
+        }
+
+        #[ink::test]
+        fn cross_scheme_signature_is_rejected() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let message = payment_message(from, to, 10_000, "mixed", 1_000);
+            let signature = sign_sr25519(&payer, &message);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    1_000,
+                    String::from("mixed"),
+                    signature,
+                    SignatureScheme::Ecdsa,
+                ),
+                Err(Error::InvalidSignature)
+            );
+
+            // An ECDSA signature from a different key cannot pass as the sr25519 payer
+            let signature = sign_ecdsa(0x22, &message);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    1_000,
+                    String::from("mixed"),
+                    signature,
+                    SignatureScheme::Ecdsa,
+                ),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(contract.balance_of(to), 0);
+        }
+
+        #[ink::test]
+        fn batch_settles_items_independently() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signed = |amount: Balance, nonce: &str| Authorization {
+                from,
+                to,
+                amount,
+                valid_until: 1_000,
+                nonce: String::from(nonce),
+                signature: sign_payment(&payer, to, amount, nonce, 1_000),
+                scheme: SignatureScheme::Sr25519,
+            };
+            let auths = vec![signed(1_000, "one"), signed(2_000, "one"), signed(3_000, "three")];
+
+            assert_eq!(
+                contract.transfer_with_authorization_batch(auths),
+                Ok(vec![Ok(()), Err(Error::NonceAlreadyUsed), Ok(())])
+            );
+            assert_eq!(contract.balance_of(to), 4_000);
+            assert_eq!(contract.balance_of(from), 96_000);
+            assert_eq!(events_of::<TransferWithAuthorization>().len(), 2);
+        }
+
+        #[ink::test]
+        fn batch_emits_summary_of_mixed_results() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signed = |amount: Balance, nonce: &str| Authorization {
+                from,
+                to,
+                amount,
+                valid_until: 1_000,
+                nonce: String::from(nonce),
+                signature: sign_payment(&payer, to, amount, nonce, 1_000),
+                scheme: SignatureScheme::Sr25519,
+            };
+            let mut tampered = signed(5_000, "tampered");
+            tampered.signature[0] ^= 0xff;
+            let auths = vec![
+                signed(1_000, "one"),
+                tampered,
+                signed(2_000, "one"),
+                signed(3_000, "three"),
+            ];
+            assert!(contract.transfer_with_authorization_batch(auths).is_ok());
+
+            let summaries = events_of::<BatchSettled>();
+            assert_eq!(summaries.len(), 1);
+            assert_eq!((summaries[0].count_success, summaries[0].count_failed), (2, 2));
+            assert_eq!(summaries[0].total_net, 990 + 2_970);
+            assert_eq!(summaries[0].total_fee, 10 + 30);
+        }
+
+        #[ink::test]
+        fn batch_item_failing_late_leaves_no_state() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 1_000), Ok(()));
+
+            let mut bad_signature = Authorization {
+                from,
+                to,
+                amount: 500,
+                valid_until: 1_000,
+                nonce: String::from("bad"),
+                signature: sign_payment(&payer, to, 500, "bad", 1_000),
+                scheme: SignatureScheme::Sr25519,
+            };
+            bad_signature.signature[0] ^= 0xff;
+            let unfunded = Authorization {
+                amount: 5_000,
+                nonce: String::from("unfunded"),
+                signature: sign_payment(&payer, to, 5_000, "unfunded", 1_000),
+                ..bad_signature.clone()
+            };
+
+            assert_eq!(
+                contract.transfer_with_authorization_batch(vec![bad_signature, unfunded]),
+                Ok(vec![
+                    Err(Error::InvalidSignature),
+                    Err(Error::PSP22(PSP22Error::InsufficientBalance)),
+                ])
+            );
+            assert!(!contract.is_nonce_used(from, String::from("unfunded")));
+            assert_eq!(contract.used_nonce_count(from), 0);
+            assert_eq!(contract.balance_of(from), 1_000);
+        }
+
+        #[ink::test]
+        fn batch_fails_as_a_whole_when_settling_an_item_fails() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            let full = AccountId::from([0x04; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            // Passes every check, but crediting the recipient overflows
+            contract.balances.insert(full, &Balance::MAX);
+
+            let signed = |to: AccountId, nonce: &str| Authorization {
+                from,
+                to,
+                amount: 1_000,
+                valid_until: 1_000,
+                nonce: String::from(nonce),
+                signature: sign_payment(&payer, to, 1_000, nonce, 1_000),
+                scheme: SignatureScheme::Sr25519,
+            };
+            // The error is returned for the whole call, which reverts the
+            // first item on chain as well
+            assert_eq!(
+                contract.transfer_with_authorization_batch(vec![
+                    signed(to, "first"),
+                    signed(full, "overflow"),
+                ]),
+                Err(Error::PSP22(PSP22Error::Overflow))
+            );
+            assert!(events_of::<BatchSettled>().is_empty());
+        }
+
+        #[ink::test]
+        fn oversized_batch_is_rejected() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let auth = Authorization {
+                from: AccountId::from([0x02; 32]),
+                to: AccountId::from([0x03; 32]),
+                amount: 1_000,
+                valid_until: 1_000,
+                nonce: String::from("n"),
+                signature: vec![0u8; 64],
+                scheme: SignatureScheme::Sr25519,
+            };
+            assert_eq!(
+                contract.transfer_with_authorization_batch(vec![auth; MAX_BATCH_SIZE + 1]),
+                Err(Error::TooManyAuthorizations)
+            );
+            assert!(events_of::<BatchSettled>().is_empty());
+        }
+
+        #[ink::test]
+        fn malformed_signatures_are_rejected_before_verification() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let valid = sign_payment(&payer, to, 1_000, "shape", 1_000);
+            let mut over_length = valid.clone();
+            over_length.push(0);
+            let mut non_canonical = valid.clone();
+            non_canonical[63] |= 0x7f;
+            let mut unmarked = valid;
+            unmarked[63] &= 0x7f;
+
+            for signature in [vec![0u8; 64], over_length, non_canonical, unmarked] {
+                assert_eq!(
+                    contract.transfer_with_authorization(
+                        from,
+                        to,
+                        1_000,
+                        1_000,
+                        String::from("shape"),
+                        signature,
+                        SignatureScheme::Sr25519,
+                    ),
+                    Err(Error::InvalidSignature)
+                );
+            }
+            // Each rejection is still recorded, with the length actually submitted
+            #[cfg(feature = "debug-events")]
+            {
+                let debug = events_of::<DebugSignature>();
+                assert!(debug.iter().all(|event| !event.signature_valid));
+                let lengths: Vec<u32> = debug.iter().map(|event| event.signature_len).collect();
+                assert_eq!(lengths, vec![64, 65, 64, 64]);
+            }
+        }
+
+        #[cfg(feature = "debug-events")]
+        #[ink::test]
+        fn debug_signature_event_is_recorded() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_payment(&payer, to, 1_000, "debug", 1_000);
+            let signature_len = signature.len() as u32;
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("debug"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+
+            let debug = events_of::<DebugSignature>();
+            assert_eq!(debug.len(), 1);
+            assert!(debug[0].signature_valid);
+            assert_eq!(debug[0].signature_len, signature_len);
+        }
+
+        #[ink::test]
+        fn batch_without_duplicates_is_detected_clean() {
+            let contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let alice = AccountId::from([0x01; 32]);
+            let bob = AccountId::from([0x02; 32]);
+
+            // Same nonce from different payers is not a duplicate
+            let auths = vec![
+                authorization(alice, "nonce-1"),
+                authorization(alice, "nonce-2"),
+                authorization(bob, "nonce-1"),
+            ];
+            assert!(!contract.batch_has_duplicate_nonces(auths));
+            assert!(!contract.batch_has_duplicate_nonces(Vec::new()));
+        }
+
+        #[ink::test]
+        fn batch_with_duplicates_is_detected() {
+            let contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let alice = AccountId::from([0x01; 32]);
+            let bob = AccountId::from([0x02; 32]);
+
+            let auths = vec![
+                authorization(alice, "nonce-1"),
+                authorization(bob, "nonce-2"),
+                authorization(alice, "nonce-1"),
+            ];
+            assert!(contract.batch_has_duplicate_nonces(auths));
+        }
+
+        #[ink::test]
+        fn scheme_cost_hints_are_ordered() {
+            let contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let sr25519 = contract.scheme_cost_hint(SignatureScheme::Sr25519 as u8);
+            let ecdsa = contract.scheme_cost_hint(SignatureScheme::Ecdsa as u8);
+            assert!(sr25519 > 0);
+            assert!(ecdsa > sr25519);
+            assert_eq!(contract.scheme_cost_hint(u8::MAX), 0);
+        }
+
+        #[ink::test]
+        fn message_preimage_matches_manual_layout() {
+            let contract = Httpusd::new(1_000, 100).unwrap();
+            let from = AccountId::from([0x01; 32]);
+            let to = AccountId::from([0x02; 32]);
+            let valid_until = 1_700_000_000_000;
+            let expected = payment_message(from, to, 12_345, "nonce-xyz", valid_until);
+
+            let preimage =
+                contract.message_preimage(from, to, 12_345, String::from("nonce-xyz"), valid_until);
+            assert_eq!(preimage, expected);
+            assert_eq!(preimage.len(), 32 + 32 + 16 + "nonce-xyz".len() + 8 + 32);
+
+            let mut blake2 = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&expected, &mut blake2);
+            let hash = contract.message_hash(
+                from,
+                to,
+                12_345,
+                String::from("nonce-xyz"),
+                valid_until,
+                SignatureScheme::Sr25519,
+            );
+            assert_eq!(hash, blake2);
+        }
+
+        #[ink::test]
+        fn signature_is_bound_to_chain_id() {
+            let mut contract = Httpusd::new_with_chain_id(1_000_000_000_000, 100, 2).unwrap();
+            assert_eq!(contract.get_chain_id(), 2);
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let other_chain = payment_message_for_chain(from, to, 1_000, "domain", 1_000, 1);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("domain"),
+                    sign_sr25519(&payer, &other_chain),
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::InvalidSignature)
+            );
+
+            let this_chain = payment_message_for_chain(from, to, 1_000, "domain", 1_000, 2);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("domain"),
+                    sign_sr25519(&payer, &this_chain),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn signature_is_bound_to_contract_address() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+
+            // Signed for the deployment at the default address...
+            let signature = sign_payment(&payer, to, 1_000, "domain", 1_000);
+
+            // ...then replayed against a deployment at another address
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("domain"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::InvalidSignature)
+            );
+        }
+
+        #[ink::test]
+        fn domain_separator_matches_recomputation() {
+            let contract = Httpusd::new_with_chain_id(1_000, 100, 42).unwrap();
+            assert_eq!(contract.domain_separator(), expected_domain_separator(42));
+            assert_ne!(contract.domain_separator(), expected_domain_separator(0));
+        }
+
+        #[ink::test]
+        fn paused_authorizations_leave_transfers_live() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            assert_eq!(contract.pause_authorizations(), Ok(()));
+            assert!(contract.is_authorizations_paused());
+
+            let signature = sign_payment(&payer, to, 1_000, "paused", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("paused"),
+                    signature.clone(),
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::AuthorizationsPaused)
+            );
+            assert_eq!(contract.transfer(accounts.bob, 500), Ok(()));
+            assert_eq!(contract.balance_of(accounts.bob), 500);
+
+            assert_eq!(contract.unpause_authorizations(), Ok(()));
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("paused"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn only_owner_can_pause_authorizations() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.pause_authorizations(), Err(Error::MissingRole));
+            assert!(!contract.is_authorizations_paused());
+        }
+
+        #[ink::test]
+        fn paused_contract_rejects_settlements() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            assert_eq!(contract.pause(), Ok(()));
+            assert!(contract.is_paused());
+            assert_eq!(events_of::<Paused>().len(), 1);
+
+            let signature = sign_payment(&payer, to, 1_000, "stop", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("stop"),
+                    signature.clone(),
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::ContractPaused)
+            );
+            // Reads keep working
+            assert_eq!(contract.balance_of(from), 100_000);
+            assert!(!contract.is_nonce_used(from, String::from("stop")));
+
+            assert_eq!(contract.unpause(), Ok(()));
+            assert_eq!(events_of::<Unpaused>().len(), 1);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("stop"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn only_owner_can_pause() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.pause(), Err(Error::MissingRole));
+            assert!(!contract.is_paused());
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(contract.pause(), Ok(()));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.unpause(), Err(Error::MissingRole));
+            assert!(contract.is_paused());
+        }
+
+        /// Deploy a contract whose owner is the sr25519 payer, funded with 100_000
+        fn contract_owned_by(payer: &schnorrkel::Keypair) -> Httpusd {
+            let owner = sr25519_account(payer);
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            assert_eq!(contract.transfer(owner, 100_000), Ok(()));
+            assert_eq!(contract.transfer_ownership(owner), Ok(()));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(owner);
+            assert_eq!(contract.accept_ownership(), Ok(()));
+            contract
+        }
+
+        #[ink::test]
+        fn owner_self_authorization_is_rejected_when_enabled() {
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            let mut contract = contract_owned_by(&payer);
+            assert_eq!(contract.set_owner_cannot_self_authorize(true), Ok(()));
+
+            let signature = sign_payment(&payer, to, 1_000, "self", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("self"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::OwnerSelfAuthorization)
+            );
+        }
+
+        #[ink::test]
+        fn owner_self_authorization_is_allowed_by_default() {
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            let mut contract = contract_owned_by(&payer);
+            assert!(!contract.get_owner_cannot_self_authorize());
+
+            let signature = sign_payment(&payer, to, 1_000, "self", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("self"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn daily_spend_limit_is_enforced_and_resets() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.set_spend_limit_per_day(Some(2_500)), Ok(()));
+
+            let settle = |contract: &mut Httpusd, nonce: &str, valid_until: u64| {
+                let signature = sign_payment(&payer, to, 1_000, nonce, valid_until);
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    valid_until,
+                    String::from(nonce),
+                    signature,
+                    SignatureScheme::Sr25519,
+                )
+            };
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert_eq!(settle(&mut contract, "day-1-a", DAY_MS * 3), Ok(()));
+            assert_eq!(settle(&mut contract, "day-1-b", DAY_MS * 3), Ok(()));
+            assert_eq!(contract.spent_today(from), 2_000);
+            assert_eq!(
+                settle(&mut contract, "day-1-c", DAY_MS * 3),
+                Err(Error::SpendLimitExceeded)
+            );
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(DAY_MS + 1_000);
+            assert_eq!(contract.spent_today(from), 0);
+            assert_eq!(settle(&mut contract, "day-1-c", DAY_MS * 3), Ok(()));
+        }
+
+        #[ink::test]
+        fn idempotent_retry_returns_recorded_result() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_payment(&payer, to, 1_000, "retry", 1_000);
+            let submit = |contract: &mut Httpusd, nonce: &str, signature: Vec<u8>, key: &str| {
+                contract.transfer_with_authorization_idempotent(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from(nonce),
+                    signature,
+                    SignatureScheme::Sr25519,
+                    String::from(key),
+                )
+            };
+
+            assert_eq!(submit(&mut contract, "retry", signature.clone(), "rpc-1"), Ok(()));
+            assert_eq!(submit(&mut contract, "retry", signature.clone(), "rpc-1"), Ok(()));
+            // Settled exactly once
+            assert_eq!(contract.balance_of(to), 990);
+            assert_eq!(contract.balance_of(from), 99_000);
+
+            // Without the key the retry is a replay
+            assert_eq!(
+                submit(&mut contract, "retry", signature.clone(), "rpc-2"),
+                Err(Error::NonceAlreadyUsed)
+            );
+
+            // Reusing the key for another authorization conflicts
+            let other = sign_payment(&payer, to, 1_000, "other", 1_000);
+            assert_eq!(
+                submit(&mut contract, "other", other, "rpc-1"),
+                Err(Error::IdempotencyKeyConflict)
+            );
+        }
+
+        #[ink::test]
+        fn idempotency_key_expires_after_window() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.set_idempotency_window(100), Ok(()));
+
+            let signature = sign_payment(&payer, to, 1_000, "retry", 10_000);
+            let submit = |contract: &mut Httpusd| {
+                contract.transfer_with_authorization_idempotent(
+                    from,
+                    to,
+                    1_000,
+                    10_000,
+                    String::from("retry"),
+                    signature.clone(),
+                    SignatureScheme::Sr25519,
+                    String::from("rpc-1"),
+                )
+            };
+
+            assert_eq!(submit(&mut contract), Ok(()));
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(101);
+            assert_eq!(submit(&mut contract), Err(Error::NonceAlreadyUsed));
+        }
+
+        fn sign_partial(
+            payer: &schnorrkel::Keypair,
+            to: AccountId,
+            max_amount: Balance,
+            nonce: &str,
+            valid_until: u64,
+        ) -> Vec<u8> {
+            let mut message = b"polkax402:partial".to_vec();
+            let from = sr25519_account(payer);
+            message.extend_from_slice(&payment_message(from, to, max_amount, nonce, valid_until));
+            sign_sr25519(payer, &message)
+        }
+
+        #[ink::test]
+        fn partial_draws_exhaust_authorization() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_partial(&payer, to, 100, "metered", 1_000);
+            let draw = |contract: &mut Httpusd, amount: Balance| {
+                contract.transfer_with_authorization_partial(
+                    from,
+                    to,
+                    100,
+                    amount,
+                    1_000,
+                    String::from("metered"),
+                    signature.clone(),
+                    SignatureScheme::Sr25519,
+                )
+            };
+
+            assert_eq!(draw(&mut contract, 30), Ok(()));
+            assert_eq!(contract.partial_remaining(from, String::from("metered")), Some(70));
+            assert!(!contract.is_nonce_used(from, String::from("metered")));
+            assert_eq!(draw(&mut contract, 30), Ok(()));
+            assert_eq!(draw(&mut contract, 41), Err(Error::DrawExceedsRemaining));
+            assert_eq!(draw(&mut contract, 40), Ok(()));
+
+            assert_eq!(contract.balance_of(to), 100);
+            assert_eq!(contract.partial_remaining(from, String::from("metered")), None);
+            assert!(contract.is_nonce_used(from, String::from("metered")));
+            assert_eq!(draw(&mut contract, 1), Err(Error::NonceAlreadyUsed));
+        }
+
+        #[ink::test]
+        fn full_authorization_cannot_be_drawn_partially() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let full = sign_payment(&payer, to, 100, "metered", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization_partial(
+                    from,
+                    to,
+                    100,
+                    30,
+                    1_000,
+                    String::from("metered"),
+                    full.clone(),
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(contract.partial_remaining(from, String::from("metered")), None);
+
+            let partial = sign_partial(&payer, to, 100, "metered", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    100,
+                    1_000,
+                    String::from("metered"),
+                    partial,
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    100,
+                    1_000,
+                    String::from("metered"),
+                    full,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn partially_drawn_authorization_cannot_settle_in_full() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_partial(&payer, to, 100, "metered", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization_partial(
+                    from,
+                    to,
+                    100,
+                    30,
+                    1_000,
+                    String::from("metered"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    100,
+                    1_000,
+                    String::from("metered"),
+                    sign_payment(&payer, to, 100, "metered", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::NonceAlreadyUsed)
+            );
+        }
+
+        #[ink::test]
+        fn get_config_reflects_constructor_and_updates() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new_with_max_fee(1_000_000, 100, 500).unwrap();
+            let expected = ContractConfig {
+                owner: accounts.alice,
+                facilitator_fee_bps: 100,
+                fee_rounding: FeeRounding::Down,
+                fee_recipient: accounts.alice,
+                paused: false,
+                max_fee_bps: 500,
+                decimals: DEFAULT_DECIMALS,
+                authorizations_paused: false,
+                fee_token: None,
+                expiry_grace_ms: 0,
+                min_amount: 0,
+                min_fee: 0,
+                chain_id: 0,
+                allow_no_expiry: false,
+                namespace_gating: false,
+                owner_cannot_self_authorize: false,
+                signing_format: SigningFormat::Raw,
+                spend_limit_per_day: None,
+                max_validity_window_ms: None,
+                max_submission_delay_ms: None,
+                late_settlement_surcharge_bps: 0,
+                allow_late_within_ms: 0,
+                fee_timelock_ms: 0,
+                pending_fee: None,
+                pending_fee_timelock: None,
+                pending_min_fee: None,
+                cancel_on_freeze: false,
+                idempotency_window_ms: DEFAULT_IDEMPOTENCY_WINDOW_MS,
+                max_supply: None,
+            };
+            assert_eq!(contract.get_config(), expected);
+
+            assert_eq!(contract.set_facilitator_fee(250), Ok(()));
+            assert_eq!(contract.set_late_settlement(200, 5_000), Ok(()));
+            assert_eq!(contract.set_spend_limit_per_day(Some(1_000)), Ok(()));
+            assert_eq!(
+                contract.get_config(),
+                ContractConfig {
+                    facilitator_fee_bps: 250,
+                    late_settlement_surcharge_bps: 200,
+                    allow_late_within_ms: 5_000,
+                    spend_limit_per_day: Some(1_000),
+                    ..expected
+                }
+            );
+        }
+
+        #[ink::test]
+        fn fee_increase_waits_for_timelock() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.set_fee_timelock(1_000), Ok(()));
+
+            let settle = |contract: &mut Httpusd, nonce: &str| {
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    10_000,
+                    String::from(nonce),
+                    sign_payment(&payer, to, 10_000, nonce, 10_000),
+                    SignatureScheme::Sr25519,
+                )
+            };
+
+            assert_eq!(contract.set_facilitator_fee(300), Ok(()));
+            assert_eq!(contract.pending_fee(), Some((300, 1_000)));
+            assert_eq!(contract.get_facilitator_fee(), 100);
+            assert!(events_of::<FeeChanged>().is_empty());
+
+            // Before the effective time the old fee is charged
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(999);
+            assert_eq!(settle(&mut contract, "before"), Ok(()));
+            assert_eq!(contract.balance_of(to), 9_900);
+
+            // From the effective time the new fee is charged and committed
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert_eq!(contract.get_facilitator_fee(), 300);
+            assert_eq!(contract.pending_fee(), None);
+            assert_eq!(settle(&mut contract, "after"), Ok(()));
+            assert_eq!(contract.balance_of(to), 9_900 + 9_700);
+            assert_eq!(contract.fee_bps_at(1_000), Some(300));
+            assert_eq!(contract.fee_bps_at(999), Some(100));
+            let changes = events_of::<FeeChanged>();
+            assert_eq!(changes.len(), 1);
+            assert_eq!((changes[0].old_bps, changes[0].new_bps), (100, 300));
+        }
+
+        #[ink::test]
+        fn fee_decrease_applies_immediately_and_cancels_pending_increase() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            assert_eq!(contract.set_fee_timelock(1_000), Ok(()));
+            assert_eq!(contract.set_facilitator_fee(300), Ok(()));
+            assert_eq!(contract.pending_fee(), Some((300, 1_000)));
+
+            assert_eq!(contract.set_facilitator_fee(50), Ok(()));
+            assert_eq!(contract.get_facilitator_fee(), 50);
+            assert_eq!(contract.pending_fee(), None);
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(2_000);
+            assert_eq!(contract.get_facilitator_fee(), 50);
+        }
+
+        #[ink::test]
+        fn fee_timelock_reduction_waits_for_current_timelock() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            assert_eq!(contract.set_fee_timelock(1_000), Ok(()));
+            assert_eq!(contract.get_fee_timelock(), 1_000);
+
+            // Dropping the timelock cannot rush an increase through
+            assert_eq!(contract.set_fee_timelock(0), Ok(()));
+            assert_eq!(contract.get_fee_timelock(), 1_000);
+            assert_eq!(contract.pending_fee_timelock(), Some((0, 1_000)));
+            assert_eq!(contract.set_facilitator_fee(300), Ok(()));
+            assert_eq!(contract.get_facilitator_fee(), 100);
+            assert_eq!(contract.pending_fee(), Some((300, 1_000)));
+            let config = contract.get_config();
+            assert_eq!(config.fee_timelock_ms, 1_000);
+            assert_eq!(config.pending_fee, Some((300, 1_000)));
+            assert_eq!(config.pending_fee_timelock, Some((0, 1_000)));
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert_eq!(contract.get_fee_timelock(), 0);
+            assert_eq!(contract.pending_fee_timelock(), None);
+            assert_eq!(contract.set_facilitator_fee(400), Ok(()));
+            assert_eq!(contract.get_facilitator_fee(), 400);
+
+            // Raising the timelock applies immediately and drops a pending reduction
+            assert_eq!(contract.set_fee_timelock(500), Ok(()));
+            assert_eq!(contract.set_fee_timelock(100), Ok(()));
+            assert_eq!(contract.pending_fee_timelock(), Some((100, 1_500)));
+            assert_eq!(contract.set_fee_timelock(2_000), Ok(()));
+            assert_eq!(contract.get_fee_timelock(), 2_000);
+            assert_eq!(contract.pending_fee_timelock(), None);
+        }
+
+        #[ink::test]
+        fn balance_of_at_returns_checkpointed_balances() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_block_number::<ink::env::DefaultEnvironment>(10);
+            let mut contract = Httpusd::new_with_checkpoints(1_000, 0).unwrap();
+
+            ink::env::test::set_block_number::<ink::env::DefaultEnvironment>(20);
+            assert_eq!(contract.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(contract.transfer(accounts.bob, 50), Ok(()));
+
+            ink::env::test::set_block_number::<ink::env::DefaultEnvironment>(30);
+            assert_eq!(contract.transfer(accounts.charlie, 200), Ok(()));
+
+            let alice = accounts.alice;
+            assert_eq!(contract.balance_of_at(alice, 9), 0);
+            assert_eq!(contract.balance_of_at(alice, 10), 1_000);
+            assert_eq!(contract.balance_of_at(alice, 19), 1_000);
+            assert_eq!(contract.balance_of_at(alice, 20), 850);
+            assert_eq!(contract.balance_of_at(alice, 25), 850);
+            assert_eq!(contract.balance_of_at(alice, 30), 650);
+            assert_eq!(contract.balance_of_at(alice, u32::MAX), 650);
+
+            assert_eq!(contract.balance_of_at(accounts.bob, 19), 0);
+            assert_eq!(contract.balance_of_at(accounts.bob, 20), 150);
+            assert_eq!(contract.balance_of_at(accounts.charlie, 29), 0);
+            assert_eq!(contract.balance_of_at(accounts.charlie, 30), 200);
+            // Two transfers in block 20 share one checkpoint
+            assert_eq!(contract.checkpoint_counts.get(accounts.bob), Some(1));
+        }
+
+        #[ink::test]
+        fn balance_of_at_is_zero_without_checkpoints() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 0).unwrap();
+            assert_eq!(contract.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(contract.balance_of_at(accounts.alice, u32::MAX), 0);
+            assert_eq!(contract.balance_of_at(accounts.bob, u32::MAX), 0);
+        }
+
+        #[ink::test]
+        fn payment_hook_is_registered_by_the_recipient() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 0).unwrap();
+            let hook = AccountId::from([0x42; 32]);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.set_payment_hook(Some(hook)), Ok(()));
+            assert_eq!(contract.get_payment_hook(accounts.bob), Some(hook));
+            assert_eq!(contract.get_payment_hook(accounts.alice), None);
+
+            assert_eq!(contract.set_payment_hook(None), Ok(()));
+            assert_eq!(contract.get_payment_hook(accounts.bob), None);
+        }
+
+        #[ink::test]
+        fn amounts_above_total_supply_fail_fast() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+
+            // Rejected before the (invalid) signature is even checked
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_001,
+                    1_000,
+                    "n1".into(),
+                    vec![0u8; 64],
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::AmountExceedsSupply)
+            );
+            assert_eq!(contract.transfer(accounts.bob, 1_001), Err(Error::AmountExceedsSupply));
+            assert_eq!(contract.approve(accounts.bob, 2_000), Ok(()));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                contract.transfer_from(accounts.alice, accounts.charlie, 1_001),
+                Err(Error::AmountExceedsSupply)
+            );
+
+            // Within supply but above the balance still reports the balance
+            assert_eq!(
+                contract.transfer(accounts.charlie, 1_000),
+                Err(Error::PSP22(PSP22Error::InsufficientBalance))
+            );
+        }
+
+        #[ink::test]
+        fn recent_payments_keep_the_latest_settlements() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 1_000_000), Ok(()));
+            assert!(contract.recent_payments(10).is_empty());
+
+            let settled = PAYMENT_HISTORY_CAPACITY + 6;
+            for i in 1..=settled {
+                let amount = Balance::from(i);
+                let nonce = format!("n{i}");
+                assert_eq!(
+                    contract.transfer_with_authorization(
+                        from,
+                        to,
+                        amount,
+                        1_000,
+                        nonce.clone(),
+                        sign_payment(&payer, to, amount, &nonce, 1_000),
+                        SignatureScheme::Sr25519,
+                    ),
+                    Ok(())
+                );
+            }
+
+            let last_three = contract.recent_payments(3);
+            let amounts: Vec<Balance> = last_three.iter().map(|r| r.net_amount).collect();
+            let settled = Balance::from(settled);
+            assert_eq!(amounts, vec![settled - 2, settled - 1, settled]);
+            let latest = format!("n{settled}");
+            assert_eq!(last_three[2].nonce_hash, contract.nonce_hash(from, latest));
+            assert_eq!((last_three[2].from, last_three[2].to), (from, to));
+
+            let all = contract.recent_payments(u32::MAX);
+            assert_eq!(all.len(), PAYMENT_HISTORY_CAPACITY as usize);
+            assert_eq!(all[0].net_amount, settled - Balance::from(PAYMENT_HISTORY_CAPACITY) + 1);
+        }
+
+        #[ink::test]
+        fn fee_rounding_modes_round_fractional_fees() {
+            // 1.5% of 1_030 is 15.45
+            let fee = |rounding: FeeRounding| {
+                let contract = Httpusd::new_with_fee_rounding(1_000_000, 150, rounding).unwrap();
+                assert_eq!(contract.get_fee_rounding(), rounding);
+                contract.compute_fee(1_030, 150)
+            };
+            assert_eq!(fee(FeeRounding::Down), 15);
+            assert_eq!(fee(FeeRounding::Up), 16);
+            assert_eq!(fee(FeeRounding::HalfUp), 15);
+
+            // 1.5% of 1_070 is 16.05, 1.5% of 1_100 is exactly 16.5
+            let contract =
+                Httpusd::new_with_fee_rounding(1_000_000, 150, FeeRounding::HalfUp).unwrap();
+            assert_eq!(contract.compute_fee(1_070, 150), 16);
+            assert_eq!(contract.compute_fee(1_100, 150), 17);
+            assert_eq!(Httpusd::new(1_000_000, 150).unwrap().get_fee_rounding(), FeeRounding::Down);
+        }
+
+        #[ink::test]
+        fn fee_rounding_never_exceeds_amount_or_overflows() {
+            let contract = Httpusd::new_with_fee_rounding(1_000_000, 100, FeeRounding::Up).unwrap();
+            assert_eq!(contract.compute_fee(1, 10_000), 1);
+            assert_eq!(contract.compute_fee(1, 1), 1);
+            assert_eq!(contract.compute_fee(Balance::MAX, 10_000), Balance::MAX);
+            assert_eq!(contract.compute_fee(Balance::MAX, 100), Balance::MAX / 100 + 1);
+        }
+
+        #[ink::test]
+        fn min_fee_floors_small_fees_up_to_the_fee_cap() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.get_min_fee(), 0);
+            assert_eq!(contract.prepare_settlement(from, to, 50, None).unwrap().facilitator_fee, 0);
+
+            assert_eq!(contract.set_min_fee(3), Ok(()));
+            // 1% of 50 rounds to zero, so the floor applies
+            let settlement = contract.prepare_settlement(from, to, 50, None).unwrap();
+            assert_eq!((settlement.facilitator_fee, settlement.net_amount), (3, 47));
+            // The floor is capped at the 10% max fee on the amount
+            let settlement = contract.prepare_settlement(from, to, 20, None).unwrap();
+            assert_eq!((settlement.facilitator_fee, settlement.net_amount), (2, 18));
+            assert_eq!(contract.set_min_fee(1_000_000), Ok(()));
+            let settlement = contract.prepare_settlement(from, to, 50, None).unwrap();
+            assert_eq!((settlement.facilitator_fee, settlement.net_amount), (5, 45));
+            assert_eq!(contract.set_min_fee(3), Ok(()));
+            // Above the floor the percentage fee is unchanged
+            let settlement = contract.prepare_settlement(from, to, 10_000, None).unwrap();
+            assert_eq!(settlement.facilitator_fee, 100);
+
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    50,
+                    1_000,
+                    String::from("small"),
+                    sign_payment(&payer, to, 50, "small", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(contract.balance_of(to), 47);
+            assert_eq!(contract.get_total_fees(), 3);
+
+            // Exempt recipients pay no floor
+            assert_eq!(contract.set_fee_exempt(to, true), Ok(()));
+            assert_eq!(contract.prepare_settlement(from, to, 50, None).unwrap().facilitator_fee, 0);
+        }
+
+        #[ink::test]
+        fn min_fee_increase_waits_for_timelock() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            assert_eq!(contract.set_fee_timelock(1_000), Ok(()));
+            assert_eq!(contract.set_min_fee(3), Ok(()));
+            assert_eq!(contract.get_min_fee(), 0);
+            assert_eq!(contract.pending_min_fee(), Some((3, 1_000)));
+            assert_eq!(contract.get_config().pending_min_fee, Some((3, 1_000)));
+            let to = AccountId::from([0x03; 32]);
+            let fee = |contract: &Httpusd| {
+                contract.prepare_settlement(accounts.alice, to, 50, None).unwrap().facilitator_fee
+            };
+            assert_eq!(fee(&contract), 0);
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert_eq!(contract.get_min_fee(), 3);
+            assert_eq!(contract.pending_min_fee(), None);
+            assert_eq!(fee(&contract), 3);
+
+            // Decreases apply immediately and cancel a pending increase
+            assert_eq!(contract.set_min_fee(5), Ok(()));
+            assert_eq!(contract.set_min_fee(1), Ok(()));
+            assert_eq!(contract.get_min_fee(), 1);
+            assert_eq!(contract.pending_min_fee(), None);
+        }
+
+        #[ink::test]
+        fn validate_config_accepts_defaults() {
+            let contract = Httpusd::new(1_000_000, 100).unwrap();
+            assert_eq!(contract.validate_config(), Ok(()));
+        }
+
+        #[ink::test]
+        fn validate_config_reports_first_inconsistency() {
+            let mut contract = Httpusd::new(1_000_000, 100).unwrap();
+
+            contract.facilitator_fee_bps = 2_000;
+            assert_eq!(contract.validate_config(), Err(Error::FeeTooHigh));
+            contract.max_fee_bps = 20_000;
+            assert_eq!(contract.validate_config(), Err(Error::FeeTooHigh));
+            contract.max_fee_bps = DEFAULT_MAX_FEE_BPS;
+            contract.facilitator_fee_bps = 100;
+
+            contract.pending_fee = Some((2_000, 1_000));
+            assert_eq!(contract.validate_config(), Err(Error::FeeTooHigh));
+            contract.pending_fee = None;
+
+            // 10% of a 100 minimum amount covers a floor of 10 but not 11
+            assert_eq!(contract.set_min_amount(100), Ok(()));
+            assert_eq!(contract.set_min_fee(11), Ok(()));
+            assert_eq!(contract.validate_config(), Err(Error::FeeTooHigh));
+            assert_eq!(contract.set_min_fee(10), Ok(()));
+            assert_eq!(contract.validate_config(), Ok(()));
+
+            contract.fee_recipient = AccountId::from([0u8; 32]);
+            assert_eq!(contract.set_spend_limit_per_day(Some(0)), Ok(()));
+            assert_eq!(contract.validate_config(), Err(Error::InvalidFeeRecipient));
+
+            assert_eq!(contract.set_facilitator_fee(0), Ok(()));
+            assert_eq!(contract.validate_config(), Err(Error::InvalidSpendLimit));
+
+            assert_eq!(contract.set_spend_limit_per_day(Some(50)), Ok(()));
+            assert_eq!(contract.validate_config(), Err(Error::InvalidSpendLimit));
+
+            assert_eq!(contract.set_spend_limit_per_day(Some(1_000)), Ok(()));
+            assert_eq!(contract.validate_config(), Ok(()));
+
+            contract.max_supply = Some(999_999);
+            assert_eq!(contract.validate_config(), Err(Error::MaxSupplyExceeded));
+            contract.max_supply = Some(1_000_000);
+            assert_eq!(contract.validate_config(), Ok(()));
+        }
+
+        #[ink::test]
+        fn fee_recipient_cannot_be_the_zero_account() {
+            let mut contract = Httpusd::new(1_000_000, 100).unwrap();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            assert_eq!(
+                contract.set_fee_recipient(AccountId::from([0u8; 32])),
+                Err(Error::InvalidFeeRecipient)
+            );
+            assert_eq!(contract.get_fee_recipient(), accounts.alice);
+        }
+
+        #[ink::test]
+        fn recipient_can_refund_settled_payment() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_payment(&payer, to, 1_000, "disputed", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("disputed"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                contract.refund(from, to, 1_000, String::from("disputed")),
+                Err(Error::CallerNotRecipient)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(to);
+            assert_eq!(contract.refund(from, to, 400, String::from("disputed")), Ok(()));
+            assert_eq!(contract.balance_of(to), 600);
+            assert_eq!(contract.balance_of(from), 99_400);
+            assert_eq!(contract.refundable_amount(from, to, String::from("disputed")), 600);
+
+            let refunds = events_of::<Refunded>();
+            assert_eq!(refunds.len(), 1);
+            assert_eq!(refunds[0].amount, 400);
+            assert_eq!(refunds[0].original_nonce, "disputed");
+        }
+
+        #[ink::test]
+        fn refund_exceeding_recipient_balance_fails() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_payment(&payer, to, 1_000, "disputed", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("disputed"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(to);
+            assert_eq!(contract.transfer(AccountId::from([0x04; 32]), 500), Ok(()));
+            assert_eq!(
+                contract.refund(from, to, 501, String::from("disputed")),
+                Err(Error::PSP22(PSP22Error::InsufficientBalance))
+            );
+            assert_eq!(contract.balance_of(to), 500);
+            assert_eq!(contract.refundable_amount(from, to, String::from("disputed")), 1_000);
+        }
+
+        #[ink::test]
+        fn refunds_are_capped_at_the_settled_amount() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.transfer(to, 10_000), Ok(()));
+
+            let signature = sign_payment(&payer, to, 1_000, "disputed", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("disputed"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(to);
+            assert_eq!(
+                contract.refund(from, to, 1_001, String::from("disputed")),
+                Err(Error::RefundExceedsPayment)
+            );
+            assert_eq!(contract.refund(from, to, 600, String::from("disputed")), Ok(()));
+            assert_eq!(
+                contract.refund(from, to, 401, String::from("disputed")),
+                Err(Error::RefundExceedsPayment)
+            );
+            assert_eq!(contract.refund(from, to, 400, String::from("disputed")), Ok(()));
+            assert_eq!(contract.balance_of(to), 10_000);
+            assert_eq!(contract.refundable_amount(from, to, String::from("disputed")), 0);
+        }
+
+        #[ink::test]
+        fn refund_requires_the_recorded_recipient() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            let other = AccountId::from([0x04; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.transfer(other, 10_000), Ok(()));
+
+            let signature = sign_payment(&payer, to, 1_000, "disputed", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("disputed"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+
+            // Another account refunding "on behalf of" the payment it never got
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(other);
+            assert_eq!(
+                contract.refund(from, other, 500, String::from("disputed")),
+                Err(Error::CallerNotRecipient)
+            );
+            assert_eq!(contract.balance_of(other), 10_000);
+        }
+
+        #[ink::test]
+        fn refund_is_blocked_while_paused() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_payment(&payer, to, 1_000, "disputed", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("disputed"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(contract.pause(), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(to);
+            assert_eq!(
+                contract.refund(from, to, 400, String::from("disputed")),
+                Err(Error::ContractPaused)
+            );
+            assert_eq!(contract.balance_of(to), 1_000);
+        }
+
+        #[ink::test]
+        fn refund_against_unused_nonce_fails() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let from = AccountId::from([0x02; 32]);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(to, 1_000), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(to);
+            assert_eq!(
+                contract.refund(from, to, 100, String::from("never-settled")),
+                Err(Error::NonceNotFound)
+            );
+            assert_eq!(contract.balance_of(to), 1_000);
+        }
+
+        #[ink::test]
+        fn settlement_event_carries_nonce_hash_topic() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_payment(&payer, to, 1_000, "topic", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("topic"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+
+            let nonce_hash = contract.nonce_hash(from, String::from("topic"));
+            let signature_topic = <TransferWithAuthorization as ink::env::Event>::SIGNATURE_TOPIC
+                .unwrap()
+                .to_vec();
+            let event = ink::env::test::recorded_events()
+                .find(|event| event.topics.first() == Some(&signature_topic))
+                .unwrap();
+            assert!(event.topics.contains(&nonce_hash.to_vec()));
+            assert_eq!(events_of::<TransferWithAuthorization>()[0].nonce_hash, nonce_hash);
+        }
+
+        #[ink::test]
+        fn amounts_below_minimum_are_rejected() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.get_min_amount(), 0);
+            assert_eq!(contract.set_min_amount(1_000), Ok(()));
+
+            let settle = |contract: &mut Httpusd, amount: Balance, nonce: &str| {
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    amount,
+                    1_000,
+                    String::from(nonce),
+                    sign_payment(&payer, to, amount, nonce, 1_000),
+                    SignatureScheme::Sr25519,
+                )
+            };
+
+            assert_eq!(settle(&mut contract, 500, "small"), Err(Error::AmountTooSmall));
+            assert!(!contract.is_nonce_used(from, String::from("small")));
+            assert_eq!(settle(&mut contract, 1_500, "large"), Ok(()));
+            assert_eq!(contract.balance_of(to), 1_500);
+        }
+
+        #[ink::test]
+        fn blocklist_rejects_sender_and_recipient() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let settle = |contract: &mut Httpusd, nonce: &str| {
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from(nonce),
+                    sign_payment(&payer, to, 1_000, nonce, 1_000),
+                    SignatureScheme::Sr25519,
+                )
+            };
+
+            assert_eq!(contract.set_blocked(from, true), Ok(()));
+            assert!(contract.is_blocked(from));
+            assert_eq!(settle(&mut contract, "a"), Err(Error::AccountBlocked));
+            assert!(!contract.is_nonce_used(from, String::from("a")));
+
+            assert_eq!(contract.set_blocked(from, false), Ok(()));
+            assert_eq!(contract.set_blocked(to, true), Ok(()));
+            assert_eq!(settle(&mut contract, "b"), Err(Error::AccountBlocked));
+
+            let failures = events_of::<PaymentFailed>();
+            assert_eq!(failures.len(), 2);
+            assert_eq!(failures[1].nonce, "b");
+            assert_eq!(failures[1].nonce_hash, contract.nonce_hash(from, String::from("b")));
+            assert_eq!(failures[1].reason, "blocked");
+
+            assert_eq!(contract.set_blocked(to, false), Ok(()));
+            assert!(!contract.is_blocked(to));
+            assert_eq!(settle(&mut contract, "b"), Ok(()));
+            assert_eq!(contract.balance_of(to), 1_000);
+        }
+
+        #[ink::test]
+        fn set_blocked_requires_owner() {
+            let mut contract = Httpusd::new(1_000_000, 0).unwrap();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(contract.set_blocked(accounts.charlie, true).is_err());
+            assert!(!contract.is_blocked(accounts.charlie));
+        }
+
+        #[ink::test]
+        fn fee_bps_at_follows_rate_changes() {
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            let mut contract = Httpusd::new(1_000_000, 100).unwrap();
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(2_000);
+            assert_eq!(contract.set_facilitator_fee(250), Ok(()));
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(3_000);
+            assert_eq!(contract.set_facilitator_fee(50), Ok(()));
+
+            assert_eq!(contract.fee_bps_at(999), None);
+            assert_eq!(contract.fee_bps_at(1_000), Some(100));
+            assert_eq!(contract.fee_bps_at(1_999), Some(100));
+            assert_eq!(contract.fee_bps_at(2_000), Some(250));
+            assert_eq!(contract.fee_bps_at(2_500), Some(250));
+            assert_eq!(contract.fee_bps_at(u64::MAX), Some(50));
+        }
+
+        #[ink::test]
+        fn fee_history_retains_bounded_window() {
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(0);
+            let mut contract = Httpusd::new(1_000_000, 0).unwrap();
+            for i in 1..=FEE_HISTORY_CAPACITY as u64 {
+                ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(i * 10);
+                assert_eq!(contract.set_facilitator_fee(i as u16), Ok(()));
+            }
+
+            assert_eq!(contract.fee_bps_at(5), None);
+            assert_eq!(contract.fee_bps_at(10), Some(1));
+            assert_eq!(contract.fee_bps_at(FEE_HISTORY_CAPACITY as u64 * 10), Some(32));
+        }
+
+        #[ink::test]
+        fn freeze_voids_outstanding_authorizations() {
+            use scale::Encode;
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let stale = sign_payment(&payer, to, 1_000, "pre-freeze", 1_000);
+            assert_eq!(contract.set_cancel_on_freeze(true), Ok(()));
+            assert_eq!(contract.set_blocked(from, true), Ok(()));
+            assert_eq!(contract.authorization_epoch(from), 1);
+            assert_eq!(contract.set_blocked(from, false), Ok(()));
+
+            let settle = |contract: &mut Httpusd, signature: Vec<u8>| {
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("pre-freeze"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                )
+            };
+            assert_eq!(settle(&mut contract, stale), Err(Error::InvalidSignature));
+
+            let mut message = payment_message(from, to, 1_000, "pre-freeze", 1_000);
+            message.extend_from_slice(&1u32.encode());
+            let fresh = sign_sr25519(&payer, &message);
+            assert_eq!(settle(&mut contract, fresh), Ok(()));
+        }
+
+        #[ink::test]
+        fn freeze_keeps_authorizations_without_cancel_on_freeze() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_payment(&payer, to, 1_000, "pre-freeze", 1_000);
+            assert_eq!(contract.set_blocked(from, true), Ok(()));
+            assert_eq!(contract.set_blocked(from, false), Ok(()));
+            assert_eq!(contract.authorization_epoch(from), 0);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("pre-freeze"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn nonce_hash_is_blake2_of_from_and_nonce() {
+            let contract = Httpusd::new(1_000_000, 0).unwrap();
+            let from = AccountId::from([0x02; 32]);
+
+            let mut data = [0x02u8; 32].to_vec();
+            data.extend_from_slice(b"nonce-1");
+            let mut expected = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&data, &mut expected);
+
+            assert_eq!(contract.nonce_hash(from, String::from("nonce-1")), expected);
+            assert_ne!(contract.nonce_hash(from, String::from("nonce-2")), expected);
+        }
+
+        #[ink::test]
+        fn transfers_reject_zero_address() {
+            let mut contract = Httpusd::new(1_000_000, 0).unwrap();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let zero = AccountId::from([0u8; 32]);
+
+            assert_eq!(
+                contract.transfer(zero, 100),
+                Err(Error::PSP22(PSP22Error::ZeroRecipientAddress))
+            );
+            assert_eq!(
+                contract.transfer_from(zero, accounts.bob, 0),
+                Err(Error::PSP22(PSP22Error::ZeroSenderAddress))
+            );
+            assert_eq!(contract.approve(accounts.bob, 100), Ok(()));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                contract.transfer_from(accounts.alice, zero, 100),
+                Err(Error::PSP22(PSP22Error::ZeroRecipientAddress))
+            );
+            assert_eq!(contract.allowance(accounts.alice, accounts.bob), 100);
+            assert_eq!(contract.balance_of(accounts.alice), 1_000_000);
+        }
+
+        #[ink::test]
+        fn authorization_rejects_zero_address() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let zero = AccountId::from([0u8; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_payment(&payer, zero, 1_000, "burn", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    zero,
+                    1_000,
+                    1_000,
+                    String::from("burn"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::PSP22(PSP22Error::ZeroRecipientAddress))
+            );
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    zero,
+                    from,
+                    1_000,
+                    1_000,
+                    String::from("burn"),
+                    vec![0u8; 64],
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::PSP22(PSP22Error::ZeroSenderAddress))
+            );
+            assert!(!contract.is_nonce_used(from, String::from("burn")));
+        }
+
+        fn sign_refund(
+            merchant: &schnorrkel::Keypair,
+            to: AccountId,
+            amount: Balance,
+            original_nonce: &str,
+        ) -> Vec<u8> {
+            use scale::Encode;
+            let mut message = b"polkax402:refund".to_vec();
+            message.extend_from_slice(&sr25519_account(merchant).encode());
+            message.extend_from_slice(&to.encode());
+            message.extend_from_slice(&amount.encode());
+            message.extend_from_slice(original_nonce.as_bytes());
+            message.extend_from_slice(&expected_domain_separator(0));
+            sign_sr25519(merchant, &message)
+        }
+
+        #[ink::test]
+        fn merchant_signed_refund_is_relayed() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let merchant_key = sr25519_keypair(0x22);
+            let merchant = sr25519_account(&merchant_key);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_payment(&payer, merchant, 1_000, "order-7", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    merchant,
+                    1_000,
+                    1_000,
+                    String::from("order-7"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+
+            let refund_signature = sign_refund(&merchant_key, from, 600, "order-7");
+            let refund = |contract: &mut Httpusd| {
+                contract.refund_with_authorization(
+                    merchant,
+                    from,
+                    600,
+                    String::from("order-7"),
+                    refund_signature.clone(),
+                    SignatureScheme::Sr25519,
+                )
+            };
+            assert_eq!(refund(&mut contract), Ok(()));
+            assert_eq!(contract.balance_of(merchant), 400);
+            assert_eq!(contract.balance_of(from), 99_600);
+            assert_eq!(refund(&mut contract), Err(Error::NonceAlreadyUsed));
+
+            let refunds = events_of::<Refunded>();
+            assert_eq!(refunds.len(), 1);
+            assert_eq!(refunds[0].from, from);
+            assert_eq!(refunds[0].to, merchant);
+        }
+
+        #[ink::test]
+        fn forged_refund_signature_is_rejected() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let merchant = sr25519_account(&sr25519_keypair(0x22));
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_payment(&payer, merchant, 1_000, "order-7", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    merchant,
+                    1_000,
+                    1_000,
+                    String::from("order-7"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+
+            // Signed by the payer rather than the merchant being debited
+            let forged = sign_refund(&payer, from, 1_000, "order-7");
+            assert_eq!(
+                contract.refund_with_authorization(
+                    merchant,
+                    from,
+                    1_000,
+                    String::from("order-7"),
+                    forged,
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(contract.balance_of(merchant), 1_000);
+        }
+
+        #[ink::test]
+        fn signature_backend_reports_host_functions() {
+            let contract = Httpusd::new(1_000_000, 0).unwrap();
+            assert_eq!(contract.signature_backend(), SIGNATURE_BACKEND_HOST);
+        }
+
+        #[ink::test]
+        fn used_nonce_count_tracks_settlements() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.used_nonce_count(from), 0);
+
+            for nonce in ["n-1", "n-2", "n-3"] {
+                let signature = sign_payment(&payer, to, 1_000, nonce, 1_000);
+                assert_eq!(
+                    contract.transfer_with_authorization(
+                        from,
+                        to,
+                        1_000,
+                        1_000,
+                        String::from(nonce),
+                        signature,
+                        SignatureScheme::Sr25519,
+                    ),
+                    Ok(())
+                );
+            }
+
+            assert_eq!(contract.used_nonce_count(from), 3);
+            assert_eq!(contract.used_nonce_count(to), 0);
+        }
+
+        #[ink::test]
+        fn late_settlement_within_grace_is_surcharged() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.set_late_settlement(200, 5_000), Ok(()));
+
+            let settle = |contract: &mut Httpusd, nonce: &str| {
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    1_000,
+                    String::from(nonce),
+                    sign_payment(&payer, to, 10_000, nonce, 1_000),
+                    SignatureScheme::Sr25519,
+                )
+            };
+
+            // On time: 1% fee only
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert_eq!(settle(&mut contract, "on-time"), Ok(()));
+            assert_eq!(contract.balance_of(to), 9_900);
+            assert!(events_of::<LateSettlement>().is_empty());
+
+            // 4s late, within the 5s grace: 1% fee + 2% surcharge
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(5_000);
+            assert_eq!(settle(&mut contract, "late"), Ok(()));
+            assert_eq!(contract.balance_of(to), 9_900 + 9_700);
+            let late = events_of::<LateSettlement>();
+            assert_eq!(late.len(), 1);
+            assert_eq!(late[0].late_by_ms, 4_000);
+            assert_eq!(late[0].surcharge_bps, 200);
+
+            // Past the grace period
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(6_001);
+            assert_eq!(settle(&mut contract, "too-late"), Err(Error::PaymentExpired));
+        }
+
+        #[ink::test]
+        fn only_owner_can_set_late_settlement() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            assert_eq!(contract.grant_role(accounts.bob, ROLE_FEE_MANAGER), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.set_late_settlement(200, 60_000), Err(Error::NotOwner));
+            assert_eq!(contract.get_late_settlement(), (0, 0));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(contract.set_late_settlement(200, 60_000), Ok(()));
+            assert_eq!(contract.get_late_settlement(), (200, 60_000));
+        }
+
+        #[ink::test]
+        fn version_and_build_features_are_reported() {
+            let contract = Httpusd::new(1_000_000, 0).unwrap();
+            assert_eq!(contract.version(), CONTRACT_VERSION);
+
+            let features = contract.build_features();
+            assert!(features.contains(&String::from("std")));
+            assert_eq!(
+                features.contains(&String::from("debug-events")),
+                cfg!(feature = "debug-events")
+            );
+        }
+
+        #[ink::test]
+        fn canceled_authorization_cannot_settle() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(from);
+            assert_eq!(contract.cancel_authorization(String::from("regret")), Ok(()));
+            assert_eq!(
+                contract.cancel_authorization(String::from("regret")),
+                Err(Error::NonceAlreadyUsed)
+            );
+            let canceled = events_of::<AuthorizationCanceled>();
+            assert_eq!(canceled.len(), 1);
+            assert_eq!(canceled[0].nonce_hash, contract.nonce_hash(from, String::from("regret")));
+            assert!(contract.is_revoked_by_hash(canceled[0].nonce_hash));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("regret"),
+                    sign_payment(&payer, to, 1_000, "regret", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::NonceAlreadyUsed)
+            );
+            assert_eq!(contract.balance_of(to), 0);
+
+            // Long after expiry the cancellation still cannot be pruned
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(DAY_MS);
+            assert_eq!(
+                contract.prune_expired_nonce(from, String::from("regret")),
+                Err(Error::NonceNotExpired)
+            );
+        }
+
+        #[ink::test]
+        fn settled_nonce_is_used_but_not_revoked() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            let nonce_hash = contract.nonce_hash(from, String::from("paid"));
+            assert!(!contract.is_revoked_by_hash(nonce_hash));
+
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("paid"),
+                    sign_payment(&payer, to, 1_000, "paid", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert!(contract.is_nonce_used(from, String::from("paid")));
+            assert!(!contract.is_revoked_by_hash(nonce_hash));
+        }
+
+        #[ink::test]
+        fn only_the_payer_can_cancel_its_nonce() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            // Someone else cancelling the same nonce string only affects their own nonce
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.cancel_authorization(String::from("mine")), Ok(()));
+            assert!(contract.is_nonce_used(accounts.bob, String::from("mine")));
+            assert!(!contract.is_nonce_used(from, String::from("mine")));
+
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("mine"),
+                    sign_payment(&payer, to, 1_000, "mine", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn scoped_b32_and_approval_nonces_can_be_canceled() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            let other = AccountId::from([0x04; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(from);
+            assert_eq!(contract.cancel_authorization_scoped(to, String::from("scoped")), Ok(()));
+            assert_eq!(contract.cancel_authorization_b32([0x42; 32]), Ok(()));
+            assert_eq!(contract.cancel_approval(String::from("approve")), Ok(()));
+            assert_eq!(
+                contract.cancel_approval(String::from("approve")),
+                Err(Error::NonceAlreadyUsed)
+            );
+            assert_eq!(events_of::<AuthorizationCanceled>().len(), 3);
+
+            assert_eq!(
+                contract.transfer_with_authorization_scoped(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("scoped"),
+                    sign_payment_scoped(&payer, to, 1_000, "scoped", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::NonceAlreadyUsed)
+            );
+            // The scoped nonce is only canceled towards `to`
+            assert_eq!(
+                contract.transfer_with_authorization_scoped(
+                    from,
+                    other,
+                    1_000,
+                    1_000,
+                    String::from("scoped"),
+                    sign_payment_scoped(&payer, other, 1_000, "scoped", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                contract.transfer_with_authorization_b32(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    [0x42; 32],
+                    sign_payment_b32(&payer, to, 1_000, [0x42; 32], 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::NonceAlreadyUsed)
+            );
+            assert_eq!(
+                contract.approve_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("approve"),
+                    sign_approval(&payer, to, 1_000, "approve", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::NonceAlreadyUsed)
+            );
+            assert_eq!(contract.allowance(from, to), 0);
+            // Each cancel only touches its own nonce domain
+            assert!(!contract.is_nonce_used(from, String::from("approve")));
+        }
+
+        #[ink::test]
+        fn nonce_status_reports_hash_and_used_flag() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let nonce = String::from("status");
+            let expected_hash = contract.compute_nonce_hash(&from, &nonce);
+            assert_eq!(contract.nonce_status(from, nonce.clone()), (expected_hash, false));
+
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    nonce.clone(),
+                    sign_payment(&payer, to, 1_000, &nonce, 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            let (hash, used) = contract.nonce_status(from, nonce.clone());
+            assert_eq!(hash, expected_hash);
+            assert_eq!(used, contract.is_nonce_used(from, nonce));
+            assert!(used);
+        }
+
+        #[ink::test]
+        fn nonce_expiry_returns_the_authorization_valid_until() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.nonce_expiry(from, String::from("n1")), None);
+
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    7_500,
+                    String::from("n1"),
+                    sign_payment(&payer, to, 1_000, "n1", 7_500),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(contract.nonce_expiry(from, String::from("n1")), Some(7_500));
+            assert_eq!(contract.nonce_expiry(to, String::from("n1")), None);
+        }
+
+        #[ink::test]
+        fn are_nonces_used_preserves_query_order() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            for nonce in ["a", "c"] {
+                assert_eq!(
+                    contract.transfer_with_authorization(
+                        from,
+                        to,
+                        1_000,
+                        1_000,
+                        String::from(nonce),
+                        sign_payment(&payer, to, 1_000, nonce, 1_000),
+                        SignatureScheme::Sr25519,
+                    ),
+                    Ok(())
+                );
+            }
+
+            let queries = vec![
+                (from, String::from("a")),
+                (from, String::from("b")),
+                (from, String::from("c")),
+                (to, String::from("a")),
+            ];
+            assert_eq!(contract.are_nonces_used(queries), Ok(vec![true, false, true, false]));
+
+            let full = vec![(from, String::from("a")); MAX_NONCE_QUERIES];
+            assert_eq!(contract.are_nonces_used(full).map(|u| u.len()), Ok(MAX_NONCE_QUERIES));
+            let oversized = vec![(from, String::from("a")); MAX_NONCE_QUERIES + 1];
+            assert_eq!(contract.are_nonces_used(oversized), Err(Error::TooManyQueries));
+        }
+
+        #[ink::test]
+        fn fee_transfer_success_collects_the_fee() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 10_000), Ok(()));
+            let fee_recipient_before = contract.balance_of(accounts.alice);
+
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    1_000,
+                    String::from("paid"),
+                    sign_payment(&payer, to, 10_000, "paid", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(contract.balance_of(to), 9_900);
+            assert_eq!(contract.balance_of(from), 0);
+            assert_eq!(contract.balance_of(accounts.alice), fee_recipient_before + 100);
+            assert_eq!(contract.get_total_fees(), 100);
+            assert!(events_of::<FeeTransferFailed>().is_empty());
+        }
+
+        #[ink::test]
+        fn payer_short_of_the_fee_is_rejected_up_front() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            // Enough for the net amount but not the fee
+            assert_eq!(contract.transfer(from, 9_950), Ok(()));
+
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    1_000,
+                    String::from("short"),
+                    sign_payment(&payer, to, 10_000, "short", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::PSP22(PSP22Error::InsufficientBalance))
+            );
+            assert_eq!(contract.balance_of(to), 0);
+            assert_eq!(contract.balance_of(from), 9_950);
+            assert!(!contract.is_nonce_used(from, String::from("short")));
+            assert!(events_of::<FeeTransferFailed>().is_empty());
+        }
+
+        #[ink::test]
+        fn fee_transfer_failure_keeps_the_payment_and_reports_the_shortfall() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 10_000), Ok(()));
+            // A fee recipient balance that cannot take the fee without overflowing
+            contract.balances.insert(accounts.alice, &Balance::MAX);
+            let fee_recipient_before = contract.balance_of(accounts.alice);
+
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    1_000,
+                    String::from("short"),
+                    sign_payment(&payer, to, 10_000, "short", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(contract.balance_of(to), 9_900);
+            assert_eq!(contract.balance_of(from), 100);
+            assert_eq!(contract.balance_of(accounts.alice), fee_recipient_before);
+            assert_eq!(contract.get_total_fees(), 0);
+
+            let failed = events_of::<FeeTransferFailed>();
+            assert_eq!(failed.len(), 1);
+            assert_eq!((failed[0].from, failed[0].fee_recipient), (from, accounts.alice));
+            assert_eq!(failed[0].nonce_hash, contract.nonce_hash(from, String::from("short")));
+            assert_eq!(failed[0].shortfall, 100);
+        }
+
+        #[ink::test]
+        fn verify_authorization_signature_checks_only_the_signature() {
+            let contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            let good = sign_payment(&payer, to, 1_000, "n1", 1_000);
+            let verify = |contract: &Httpusd, amount: Balance, signature: &[u8]| {
+                contract.verify_authorization_signature(
+                    from,
+                    to,
+                    amount,
+                    String::from("n1"),
+                    1_000,
+                    signature.to_vec(),
+                    SignatureScheme::Sr25519,
+                )
+            };
+
+            let events_before = ink::env::test::recorded_events().count();
+            assert!(verify(&contract, 1_000, &good));
+            assert!(!verify(&contract, 999, &good));
+            let mut tampered = good.clone();
+            tampered[0] ^= 0xff;
+            assert!(!verify(&contract, 1_000, &tampered));
+            assert!(!verify(&contract, 1_000, &good[..63]));
+            assert_eq!(ink::env::test::recorded_events().count(), events_before);
+
+            // Still true once expired, since only the signature is checked
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(5_000);
+            assert!(verify(&contract, 1_000, &good));
+        }
+
+        #[ink::test]
+        fn balances_of_preserves_account_order() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 0).unwrap();
+            assert_eq!(contract.transfer(accounts.charlie, 300), Ok(()));
+
+            let queried = vec![accounts.bob, accounts.alice, accounts.charlie, accounts.bob];
+            assert_eq!(contract.balances_of(queried), Ok(vec![0, 700, 300, 0]));
+            assert_eq!(contract.balances_of(Vec::new()), Ok(Vec::new()));
+
+            let full = vec![accounts.alice; MAX_BALANCE_QUERIES];
+            assert_eq!(contract.balances_of(full).map(|b| b.len()), Ok(MAX_BALANCE_QUERIES));
+            let oversized = vec![accounts.alice; MAX_BALANCE_QUERIES + 1];
+            assert_eq!(contract.balances_of(oversized), Err(Error::TooManyQueries));
+        }
+
+        #[ink::test]
+        fn is_authorization_expired_uses_contract_clock() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(10_000);
+            assert!(contract.is_authorization_expired(9_999));
+            assert!(!contract.is_authorization_expired(10_000));
+            assert!(!contract.is_authorization_expired(20_000));
+
+            assert_eq!(contract.set_expiry_grace(5_000), Ok(()));
+            assert!(!contract.is_authorization_expired(9_999));
+            assert!(contract.is_authorization_expired(4_999));
+        }
+
+        #[ink::test]
+        fn expiry_grace_absorbs_clock_skew() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.get_expiry_grace(), 0);
+            assert_eq!(contract.set_expiry_grace(5_000), Ok(()));
+
+            let settle = |contract: &mut Httpusd, nonce: &str| {
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    1_000,
+                    String::from(nonce),
+                    sign_payment(&payer, to, 10_000, nonce, 1_000),
+                    SignatureScheme::Sr25519,
+                )
+            };
+
+            // 3s past nominal expiry, within the 5s grace: settles at the normal fee
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(4_000);
+            assert_eq!(settle(&mut contract, "skewed"), Ok(()));
+            assert_eq!(contract.balance_of(to), 9_900);
+            assert!(events_of::<LateSettlement>().is_empty());
+
+            // 10s past nominal expiry
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(11_000);
+            assert_eq!(settle(&mut contract, "stale"), Err(Error::PaymentExpired));
+        }
+
+        #[ink::test]
+        fn non_owner_cannot_set_expiry_grace() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.set_expiry_grace(5_000), Err(Error::NotOwner));
+            assert_eq!(contract.get_expiry_grace(), 0);
+        }
+
+        #[ink::test]
+        fn expired_nonce_cannot_be_pruned_within_grace() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.set_late_settlement(200, 5_000), Ok(()));
+
+            let signature = sign_payment(&payer, to, 1_000, "grace", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("grace"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(2_000);
+            assert_eq!(
+                contract.prune_expired_nonce(from, String::from("grace")),
+                Err(Error::NonceNotExpired)
+            );
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(6_001);
+            assert_eq!(contract.prune_expired_nonce(from, String::from("grace")), Ok(()));
+        }
+
+        #[ink::test]
+        fn wrapped_bytes_format_verifies_extension_signatures() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.set_signing_format(SigningFormat::WrappedBytes), Ok(()));
+
+            let raw = sign_payment(&payer, to, 1_000, "wallet", 1_000);
+            let mut wrapped_message = b"<Bytes>".to_vec();
+            wrapped_message.extend_from_slice(&payment_message(from, to, 1_000, "wallet", 1_000));
+            wrapped_message.extend_from_slice(b"</Bytes>");
+            let wrapped = sign_sr25519(&payer, &wrapped_message);
+
+            let settle = |contract: &mut Httpusd, signature: Vec<u8>| {
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("wallet"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                )
+            };
+            assert_eq!(settle(&mut contract, raw), Err(Error::InvalidSignature));
+            assert_eq!(settle(&mut contract, wrapped), Ok(()));
+        }
+
+        #[ink::test]
+        fn max_validity_window_rejects_long_lived_authorizations() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.set_max_validity_window(Some(3_600_000)), Ok(()));
+
+            let now = 1_000_000;
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(now);
+            let settle = |contract: &mut Httpusd, nonce: &str, valid_until: u64| {
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    valid_until,
+                    String::from(nonce),
+                    sign_payment(&payer, to, 1_000, nonce, valid_until),
+                    SignatureScheme::Sr25519,
+                )
+            };
+
+            assert_eq!(settle(&mut contract, "short", now + 1_800_000), Ok(()));
+            assert_eq!(
+                settle(&mut contract, "long", now + 7_200_000),
+                Err(Error::ValidityTooLong)
+            );
+
+            assert_eq!(contract.set_max_validity_window(None), Ok(()));
+            assert_eq!(settle(&mut contract, "long", now + 7_200_000), Ok(()));
+        }
+
+        #[ink::test]
+        fn expiry_semantics_match_settlement_behaviour() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            assert_eq!(contract.expiry_semantics(), (EXPIRY_KIND_TIMESTAMP, true));
+
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            // Inclusive: settling at exactly valid_until succeeds
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            let signature = sign_payment(&payer, to, 1_000, "edge", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("edge"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+
+            assert_eq!(contract.set_allow_no_expiry(true), Ok(()));
+            assert_eq!(contract.expiry_semantics(), (EXPIRY_KIND_TIMESTAMP, true));
+        }
+
+        #[ink::test]
+        fn simulate_sequence_tracks_hypothetical_balance() {
+            let mut contract = Httpusd::new(1_000_000, 100).unwrap();
+            let from = AccountId::from([0x02; 32]);
+            assert_eq!(contract.transfer(from, 1_000), Ok(()));
+
+            let fits = vec![(400, true), (300, false), (300, true)];
+            assert_eq!(contract.simulate_sequence(from, fits), Ok(0));
+
+            let exhausts = vec![(400, true), (700, true), (100, true)];
+            assert_eq!(
+                contract.simulate_sequence(from, exhausts),
+                Err(Error::PSP22(PSP22Error::InsufficientBalance))
+            );
+            assert_eq!(contract.balance_of(from), 1_000);
+        }
+
+        #[ink::test]
+        fn simulate_sequence_returns_fees_to_fee_recipient() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contract = Httpusd::new(10_000, 100).unwrap();
+            assert_eq!(contract.get_fee_recipient(), accounts.alice);
+
+            let steps = vec![(1_000, true), (1_000, false)];
+            assert_eq!(contract.simulate_sequence(accounts.alice, steps), Ok(8_010));
+        }
+
+        #[ink::test]
+        fn simulate_sequence_matches_settlement_with_min_fee_and_exemptions() {
+            let mut contract = Httpusd::new(1_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 10_000), Ok(()));
+            assert_eq!(contract.set_fee_recipient(from), Ok(()));
+            // 1% of 100 is 1; the floor raises it to 5
+            assert_eq!(contract.set_min_fee(5), Ok(()));
+            let settle = |contract: &mut Httpusd, nonce: &str| {
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    100,
+                    1_000,
+                    String::from(nonce),
+                    sign_payment(&payer, to, 100, nonce, 1_000),
+                    SignatureScheme::Sr25519,
+                )
+            };
+
+            assert_eq!(contract.simulate_sequence(from, vec![(100, true)]), Ok(9_905));
+            assert_eq!(settle(&mut contract, "floor"), Ok(()));
+            assert_eq!(contract.balance_of(from), 9_905);
+
+            // An exempt payer pays no fee, so none comes back to it
+            assert_eq!(contract.set_payer_fee_exempt(from, true), Ok(()));
+            assert_eq!(contract.simulate_sequence(from, vec![(100, true)]), Ok(9_805));
+            assert_eq!(settle(&mut contract, "exempt"), Ok(()));
+            assert_eq!(contract.balance_of(from), 9_805);
+
+            assert_eq!(contract.set_min_amount(200), Ok(()));
+            assert_eq!(
+                contract.simulate_sequence(from, vec![(100, true)]),
+                Err(Error::AmountTooSmall)
+            );
+        }
+
+        #[ink::test]
+        fn set_facilitator_fee_emits_fee_changed() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000, 100).unwrap();
+            assert_eq!(contract.set_facilitator_fee(250), Ok(()));
+
+            let changes = events_of::<FeeChanged>();
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0].old_bps, 100);
+            assert_eq!(changes[0].new_bps, 250);
+            assert_eq!(changes[0].changed_by, accounts.alice);
+        }
+
+        #[ink::test]
+        fn admin_setters_emit_config_changed() {
+            let mut contract = Httpusd::new(1_000_000, 100).unwrap();
+            assert_eq!(contract.set_spend_limit_per_day(Some(1_000)), Ok(()));
+            assert_eq!(contract.set_allow_no_expiry(true), Ok(()));
+
+            let settings: Vec<String> =
+                events_of::<ConfigChanged>().into_iter().map(|e| e.setting).collect();
+            assert_eq!(settings, ["spend_limit_per_day", "allow_no_expiry"]);
+        }
+
+        #[ink::test]
+        fn deployer_holds_all_roles() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contract = Httpusd::new(1_000_000, 100).unwrap();
+            for role in [ROLE_ADMIN, ROLE_FEE_MANAGER, ROLE_PAUSER, ROLE_BLOCKLIST_MANAGER] {
+                assert!(contract.has_role(accounts.alice, role));
+                assert!(!contract.has_role(accounts.bob, role));
+            }
+        }
+
+        #[ink::test]
+        fn fee_manager_can_change_fees_but_not_pause() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000, 100).unwrap();
+            assert_eq!(contract.grant_role(accounts.bob, ROLE_FEE_MANAGER), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.set_facilitator_fee(250), Ok(()));
+            assert_eq!(contract.get_facilitator_fee(), 250);
+            assert_eq!(contract.pause(), Err(Error::MissingRole));
+            assert_eq!(contract.grant_role(accounts.bob, ROLE_PAUSER), Err(Error::MissingRole));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(contract.revoke_role(accounts.bob, ROLE_FEE_MANAGER), Ok(()));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.set_facilitator_fee(300), Err(Error::MissingRole));
+        }
+
+        #[ink::test]
+        fn ownership_transfer_moves_owner_roles() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000, 100).unwrap();
+            assert_eq!(contract.transfer_ownership(accounts.bob), Ok(()));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.accept_ownership(), Ok(()));
+
+            assert!(contract.has_role(accounts.bob, ROLE_PAUSER));
+            assert!(!contract.has_role(accounts.alice, ROLE_PAUSER));
+        }
+
+        #[ink::test]
+        fn fee_exempt_recipient_receives_full_amount() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let exempt = AccountId::from([0x03; 32]);
+            let regular = AccountId::from([0x04; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.set_fee_exempt(exempt, true), Ok(()));
+            assert!(contract.is_fee_exempt(exempt));
+            assert!(!contract.is_fee_exempt(regular));
+
+            for (to, nonce) in [(exempt, "exempt"), (regular, "regular")] {
+                let signature = sign_payment(&payer, to, 10_000, nonce, 1_000);
+                assert_eq!(
+                    contract.transfer_with_authorization(
+                        from,
+                        to,
+                        10_000,
+                        1_000,
+                        String::from(nonce),
+                        signature,
+                        SignatureScheme::Sr25519,
+                    ),
+                    Ok(())
+                );
+            }
+
+            assert_eq!(contract.balance_of(exempt), 10_000);
+            assert_eq!(contract.balance_of(regular), 9_900);
+            let settled = events_of::<TransferWithAuthorization>();
+            assert_eq!(settled[0].facilitator_fee, 0);
+            assert_eq!(settled[1].facilitator_fee, 100);
+        }
+
+        #[ink::test]
+        fn fee_exempt_payer_pays_no_fee() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let partner = sr25519_keypair(0x11);
+            let customer = sr25519_keypair(0x12);
+            let to = AccountId::from([0x03; 32]);
+            let other = AccountId::from([0x04; 32]);
+            assert_eq!(contract.transfer(sr25519_account(&partner), 100_000), Ok(()));
+            assert_eq!(contract.transfer(sr25519_account(&customer), 100_000), Ok(()));
+            assert_eq!(contract.set_payer_fee_exempt(sr25519_account(&partner), true), Ok(()));
+            assert!(contract.is_payer_fee_exempt(sr25519_account(&partner)));
+            assert!(!contract.is_payer_fee_exempt(sr25519_account(&customer)));
+
+            for (payer, to) in [(&partner, to), (&customer, other)] {
+                let signature = sign_payment(payer, to, 10_000, "pay", 1_000);
+                assert_eq!(
+                    contract.transfer_with_authorization(
+                        sr25519_account(payer),
+                        to,
+                        10_000,
+                        1_000,
+                        String::from("pay"),
+                        signature,
+                        SignatureScheme::Sr25519,
+                    ),
+                    Ok(())
+                );
+            }
+
+            assert_eq!(contract.balance_of(to), 10_000);
+            assert_eq!(contract.balance_of(other), 9_900);
+            let settled = events_of::<TransferWithAuthorization>();
+            assert_eq!(settled[0].facilitator_fee, 0);
+            assert_eq!(settled[1].facilitator_fee, 100);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.set_payer_fee_exempt(accounts.bob, true), Err(Error::MissingRole));
+        }
+
+        fn sign_payment_v2(
+            payer: &schnorrkel::Keypair,
+            to: AccountId,
+            amount: Balance,
+            nonce: &str,
+            valid_until: u64,
+            issued_at: u64,
+        ) -> Vec<u8> {
+            use scale::Encode;
+            let from = sr25519_account(payer);
+            let mut message = b"polkax402:v2".to_vec();
+            message.extend_from_slice(&payment_message(from, to, amount, nonce, valid_until));
+            message.extend_from_slice(&issued_at.encode());
+            sign_sr25519(payer, &message)
+        }
+
+        #[ink::test]
+        fn v2_rejects_stale_signatures() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.set_max_submission_delay(Some(60_000)), Ok(()));
+
+            let now = 10_000_000;
+            let valid_until = now + 3_600_000;
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(now);
+            let settle = |contract: &mut Httpusd, nonce: &str, issued_at: u64| {
+                contract.transfer_with_authorization_v2(
+                    from,
+                    to,
+                    1_000,
+                    valid_until,
+                    issued_at,
+                    String::from(nonce),
+                    sign_payment_v2(&payer, to, 1_000, nonce, valid_until, issued_at),
+                    SignatureScheme::Sr25519,
+                )
+            };
+
+            assert_eq!(settle(&mut contract, "fresh", now - 5_000), Ok(()));
+            assert_eq!(
+                settle(&mut contract, "stale", now - 600_000),
+                Err(Error::SubmissionTooLate)
+            );
+            assert_eq!(contract.balance_of(to), 1_000);
+        }
+
+        #[ink::test]
+        fn v1_signature_does_not_verify_as_v2() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_payment(&payer, to, 1_000, "v1", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization_v2(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    0,
+                    String::from("v1"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::InvalidSignature)
+            );
+        }
+
+        #[ink::test]
+        fn validate_authorization_preflights_without_settling() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_payment(&payer, to, 1_000, "preflight", 1_000);
+            let validate = |contract: &Httpusd, signature: Vec<u8>| {
+                contract.validate_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("preflight"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                )
+            };
+
+            assert_eq!(validate(&contract, vec![0u8; 64]), Err(Error::InvalidSignature));
+            assert_eq!(validate(&contract, signature.clone()), Ok(()));
+            assert!(!contract.is_nonce_used(from, String::from("preflight")));
+            assert_eq!(contract.balance_of(to), 0);
+
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("preflight"),
+                    signature.clone(),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(validate(&contract, signature), Err(Error::NonceAlreadyUsed));
+        }
+
+        /// Register `from` as a 2-of-3 multisig over keypairs seeded 0x21..=0x23
+        fn multisig_payer(contract: &mut Httpusd, from: AccountId) -> Vec<schnorrkel::Keypair> {
+            let keys: Vec<_> = (0x21..=0x23).map(sr25519_keypair).collect();
+            let signers = keys.iter().map(sr25519_account).collect();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(from);
+            assert_eq!(contract.set_multisig(signers, 2), Ok(()));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(
+                ink::env::test::default_accounts::<ink::env::DefaultEnvironment>().alice,
+            );
+            keys
+        }
+
+        #[ink::test]
+        fn multisig_two_of_three_settles() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let from = AccountId::from([0x02; 32]);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            let keys = multisig_payer(&mut contract, from);
+
+            let message = payment_message(from, to, 5_000, "treasury", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization_multisig(
+                    from,
+                    to,
+                    5_000,
+                    1_000,
+                    String::from("treasury"),
+                    vec![sr25519_account(&keys[0]), sr25519_account(&keys[2])],
+                    vec![sign_sr25519(&keys[0], &message), sign_sr25519(&keys[2], &message)],
+                ),
+                Ok(())
+            );
+            assert_eq!(contract.balance_of(to), 5_000);
+        }
+
+        #[ink::test]
+        fn multisig_rejects_insufficient_and_duplicated_signers() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let from = AccountId::from([0x02; 32]);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            let keys = multisig_payer(&mut contract, from);
+
+            let message = payment_message(from, to, 5_000, "treasury", 1_000);
+            let signer = sr25519_account(&keys[1]);
+            let signature = sign_sr25519(&keys[1], &message);
+            let settle =
+                |contract: &mut Httpusd, signers: Vec<AccountId>, signatures: Vec<Vec<u8>>| {
+                    contract.transfer_with_authorization_multisig(
+                        from,
+                        to,
+                        5_000,
+                        1_000,
+                        String::from("treasury"),
+                        signers,
+                        signatures,
+                    )
+                };
+
+            // 1-of-3 against the registered 2-of-3
+            assert_eq!(
+                settle(&mut contract, vec![signer], vec![signature.clone()]),
+                Err(Error::ThresholdNotMet)
+            );
+            // The same signer twice counts once
+            assert_eq!(
+                settle(&mut contract, vec![signer, signer], vec![signature.clone(), signature]),
+                Err(Error::ThresholdNotMet)
+            );
+            assert_eq!(contract.balance_of(to), 0);
+            assert!(!contract.is_nonce_used(from, String::from("treasury")));
+        }
+
+        #[ink::test]
+        fn set_multisig_validates_configuration() {
+            let mut contract = Httpusd::new(1_000_000, 0).unwrap();
+            let signers = vec![AccountId::from([0x21; 32]), AccountId::from([0x22; 32])];
+            assert_eq!(
+                contract.set_multisig(signers.clone(), 3),
+                Err(Error::InvalidMultisigConfig)
+            );
+            assert_eq!(
+                contract.set_multisig(vec![signers[0], signers[0]], 1),
+                Err(Error::InvalidMultisigConfig)
+            );
+            assert_eq!(contract.set_multisig(signers.clone(), 2), Ok(()));
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            assert_eq!(contract.get_multisig(accounts.alice), Some((signers, 2)));
+        }
+
+        #[ink::test]
+        fn settlement_totals_accumulate() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            for (amount, nonce) in [(10_000, "a"), (5_000, "b"), (2_000, "c")] {
+                let signature = sign_payment(&payer, to, amount, nonce, 1_000);
+                assert_eq!(
+                    contract.transfer_with_authorization(
+                        from,
+                        to,
+                        amount,
+                        1_000,
+                        String::from(nonce),
+                        signature,
+                        SignatureScheme::Sr25519,
+                    ),
+                    Ok(())
+                );
+            }
+
+            let settled = events_of::<TransferWithAuthorization>();
+            let net: Balance = settled.iter().map(|e| e.amount).sum();
+            let fees: Balance = settled.iter().map(|e| e.facilitator_fee).sum();
+            assert_eq!(contract.get_total_volume(), net);
+            assert_eq!(contract.get_total_fees(), fees);
+            assert_eq!(contract.get_total_volume(), 16_830);
+            assert_eq!(contract.get_total_fees(), 170);
+        }
+
+        fn sign_payment_b32(
+            payer: &schnorrkel::Keypair,
+            to: AccountId,
+            amount: Balance,
+            nonce: [u8; 32],
+            valid_until: u64,
+        ) -> Vec<u8> {
+            use scale::Encode;
+            let mut message = b"polkax402:b32".to_vec();
+            message.extend_from_slice(&sr25519_account(payer).encode());
+            message.extend_from_slice(&to.encode());
+            message.extend_from_slice(&amount.encode());
+            message.extend_from_slice(&nonce);
+            message.extend_from_slice(&valid_until.encode());
+            message.extend_from_slice(&expected_domain_separator(0));
+            sign_sr25519(payer, &message)
+        }
+
+        #[ink::test]
+        fn b32_nonce_is_replay_protected_and_tracked_separately() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
 
-            let nonce_hash = contract.compute_nonce_hash(&account, &nonce);
-            contract.used_nonces.insert(nonce_hash, &true);
+            let nonce = [b'a'; 32];
+            let signature = sign_payment_b32(&payer, to, 1_000, nonce, 1_000);
+            let settle = |contract: &mut Httpusd| {
+                contract.transfer_with_authorization_b32(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    nonce,
+                    signature.clone(),
+                    SignatureScheme::Sr25519,
+                )
+            };
 
-            assert!(contract.is_nonce_used(account, nonce));
+            assert_eq!(settle(&mut contract), Ok(()));
+            assert!(contract.is_nonce_used_b32(from, nonce));
+            assert_eq!(settle(&mut contract), Err(Error::NonceAlreadyUsed));
+
+            // The same 32 bytes as a String nonce are a distinct nonce
+            let as_string = String::from_utf8(nonce.to_vec()).unwrap();
+            assert!(!contract.is_nonce_used(from, as_string.clone()));
+            let signature = sign_payment(&payer, to, 1_000, &as_string, 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    as_string,
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+
+            let settled = events_of::<TransferWithAuthorization>();
+            assert_eq!(settled[0].nonce, format!("0x{}", "61".repeat(32)));
+        }
+
+        #[ink::test]
+        fn b32_signature_does_not_verify_as_string_nonce() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let nonce = [b'a'; 32];
+            let signature = sign_payment_b32(&payer, to, 1_000, nonce, 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from_utf8(nonce.to_vec()).unwrap(),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::InvalidSignature)
+            );
+        }
+
+        fn sign_payment_scoped(
+            payer: &schnorrkel::Keypair,
+            to: AccountId,
+            amount: Balance,
+            nonce: &str,
+            valid_until: u64,
+        ) -> Vec<u8> {
+            let mut message = b"polkax402:scoped".to_vec();
+            let payment = payment_message(sr25519_account(payer), to, amount, nonce, valid_until);
+            message.extend_from_slice(&payment);
+            sign_sr25519(payer, &message)
+        }
+
+        fn sign_approval(
+            owner: &schnorrkel::Keypair,
+            spender: AccountId,
+            value: Balance,
+            nonce: &str,
+            valid_until: u64,
+        ) -> Vec<u8> {
+            let mut message = b"polkax402:approve".to_vec();
+            let owner_account = sr25519_account(owner);
+            let approval = payment_message(owner_account, spender, value, nonce, valid_until);
+            message.extend_from_slice(&approval);
+            sign_sr25519(owner, &message)
+        }
+
+        #[ink::test]
+        fn approve_with_authorization_sets_allowance_once() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let owner_key = sr25519_keypair(0x11);
+            let owner = sr25519_account(&owner_key);
+            let spender = accounts.bob;
+            let approve = |contract: &mut Httpusd| {
+                contract.approve_with_authorization(
+                    owner,
+                    spender,
+                    5_000,
+                    1_000,
+                    String::from("a1"),
+                    sign_approval(&owner_key, spender, 5_000, "a1", 1_000),
+                    SignatureScheme::Sr25519,
+                )
+            };
+
+            // Submitted by a third party, who pays the gas
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(approve(&mut contract), Ok(()));
+            assert_eq!(contract.allowance(owner, spender), 5_000);
+            assert!(contract.is_approval_nonce_used(owner, String::from("a1")));
+            let approvals = events_of::<Approval>();
+            assert_eq!(approvals.last().unwrap().owner, owner);
+            assert_eq!(approvals.last().unwrap().value, 5_000);
+
+            assert_eq!(approve(&mut contract), Err(Error::NonceAlreadyUsed));
+        }
+
+        #[ink::test]
+        fn approval_and_transfer_nonces_do_not_collide() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("n1"),
+                    sign_payment(&payer, to, 1_000, "n1", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert!(!contract.is_approval_nonce_used(from, String::from("n1")));
+
+            // A transfer signature does not verify as an approval
+            assert_eq!(
+                contract.approve_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("n2"),
+                    sign_payment(&payer, to, 1_000, "n2", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(
+                contract.approve_with_authorization(
+                    from,
+                    accounts.bob,
+                    1_000,
+                    1_000,
+                    String::from("n1"),
+                    sign_approval(&payer, accounts.bob, 1_000, "n1", 1_000),
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(contract.allowance(from, accounts.bob), 1_000);
+        }
+
+        #[ink::test]
+        fn scoped_nonce_may_repeat_across_recipients() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let alice = AccountId::from([0x03; 32]);
+            let bob = AccountId::from([0x04; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let settle = |contract: &mut Httpusd, to: AccountId| {
+                contract.transfer_with_authorization_scoped(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("session"),
+                    sign_payment_scoped(&payer, to, 1_000, "session", 1_000),
+                    SignatureScheme::Sr25519,
+                )
+            };
+
+            assert_eq!(settle(&mut contract, alice), Ok(()));
+            assert_eq!(settle(&mut contract, bob), Ok(()));
+            assert_eq!(contract.balance_of(alice), 1_000);
+            assert_eq!(contract.balance_of(bob), 1_000);
+            assert!(contract.is_nonce_used_scoped(from, alice, String::from("session")));
+            assert!(!contract.is_nonce_used(from, String::from("session")));
+
+            // A true replay to the same recipient still fails
+            assert_eq!(settle(&mut contract, alice), Err(Error::NonceAlreadyUsed));
+        }
+
+        #[ink::test]
+        fn scoped_signature_does_not_verify_as_regular() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_payment_scoped(&payer, to, 1_000, "session", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("session"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::InvalidSignature)
+            );
+        }
+
+        fn sign_payment_versioned(
+            payer: &schnorrkel::Keypair,
+            to: AccountId,
+            amount: Balance,
+            nonce: &str,
+            valid_until: u64,
+            version: u8,
+        ) -> Vec<u8> {
+            let mut message = vec![version];
+            let payment = payment_message(sr25519_account(payer), to, amount, nonce, valid_until);
+            message.extend_from_slice(&payment);
+            sign_sr25519(payer, &message)
+        }
+
+        #[ink::test]
+        fn message_version_must_be_accepted() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert!(contract.is_message_version_accepted(MESSAGE_VERSION_1));
+            assert!(!contract.is_message_version_accepted(2));
+
+            let settle = |contract: &mut Httpusd, nonce: &str| {
+                contract.transfer_with_authorization_versioned(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from(nonce),
+                    sign_payment_versioned(&payer, to, 1_000, nonce, 1_000, MESSAGE_VERSION_1),
+                    SignatureScheme::Sr25519,
+                    MESSAGE_VERSION_1,
+                )
+            };
+            assert_eq!(settle(&mut contract, "v1"), Ok(()));
+
+            // Migrate to v2 only
+            assert_eq!(contract.set_message_version_accepted(2, true), Ok(()));
+            assert_eq!(contract.set_message_version_accepted(MESSAGE_VERSION_1, false), Ok(()));
+            assert_eq!(settle(&mut contract, "v1-late"), Err(Error::UnsupportedMessageVersion));
+            assert!(!contract.is_nonce_used(from, String::from("v1-late")));
+        }
+
+        #[ink::test]
+        fn message_version_is_bound_into_signature() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.set_message_version_accepted(2, true), Ok(()));
+
+            // Signed as v1, submitted as v2
+            let signature = sign_payment_versioned(&payer, to, 1_000, "swap", 1_000, 1);
+            assert_eq!(
+                contract.transfer_with_authorization_versioned(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("swap"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                    2,
+                ),
+                Err(Error::InvalidSignature)
+            );
+        }
+
+        fn sign_split(
+            payer: &schnorrkel::Keypair,
+            amount: Balance,
+            nonce: &str,
+            valid_until: u64,
+            recipients: &[(AccountId, Balance)],
+        ) -> Vec<u8> {
+            use scale::Encode;
+            let mut recipients_hash = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(
+                &recipients.encode(),
+                &mut recipients_hash,
+            );
+            let mut message = b"polkax402:split".to_vec();
+            message.extend_from_slice(&payment_message(
+                sr25519_account(payer),
+                AccountId::from(recipients_hash),
+                amount,
+                nonce,
+                valid_until,
+            ));
+            sign_sr25519(payer, &message)
+        }
+
+        #[ink::test]
+        fn split_payment_settles_each_share() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let marketplace = AccountId::from([0x03; 32]);
+            let seller = AccountId::from([0x04; 32]);
+            let affiliate = AccountId::from([0x05; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let recipients = vec![(marketplace, 1_000), (seller, 8_000), (affiliate, 1_000)];
+            let signature = sign_split(&payer, 10_000, "split", 1_000, &recipients);
+            assert_eq!(
+                contract.transfer_with_authorization_split(
+                    from,
+                    10_000,
+                    1_000,
+                    String::from("split"),
+                    signature.clone(),
+                    SignatureScheme::Sr25519,
+                    recipients.clone(),
+                ),
+                Ok(())
+            );
+            assert_eq!(contract.balance_of(marketplace), 990);
+            assert_eq!(contract.balance_of(seller), 7_920);
+            assert_eq!(contract.balance_of(affiliate), 990);
+            assert_eq!(contract.balance_of(from), 90_000);
+            assert!(contract.is_nonce_used(from, String::from("split")));
+            assert_eq!(contract.used_nonce_count(from), 1);
+            assert_eq!(events_of::<TransferWithAuthorization>().len(), 3);
+
+            // The whole split is one authorization
+            assert_eq!(
+                contract.transfer_with_authorization_split(
+                    from,
+                    10_000,
+                    1_000,
+                    String::from("split"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                    recipients,
+                ),
+                Err(Error::NonceAlreadyUsed)
+            );
+        }
+
+        #[ink::test]
+        fn split_payment_rejects_bad_shares() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let seller = AccountId::from([0x04; 32]);
+            let affiliate = AccountId::from([0x05; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let mut split = |recipients: Vec<(AccountId, Balance)>| {
+                let signature = sign_split(&payer, 10_000, "split", 1_000, &recipients);
+                contract.transfer_with_authorization_split(
+                    from,
+                    10_000,
+                    1_000,
+                    String::from("split"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                    recipients,
+                )
+            };
+
+            // Shares add up to more than the signed total
+            assert_eq!(
+                split(vec![(seller, 9_000), (affiliate, 2_000)]),
+                Err(Error::SplitAmountMismatch)
+            );
+            assert_eq!(split(vec![(seller, 5_000), (seller, 5_000)]), Err(Error::InvalidSplit));
+            assert_eq!(split(Vec::new()), Err(Error::InvalidSplit));
+            assert_eq!(
+                split(vec![(seller, 5_000), (AccountId::from([0u8; 32]), 5_000)]),
+                Err(Error::PSP22(PSP22Error::ZeroRecipientAddress))
+            );
+            assert_eq!(contract.balance_of(from), 100_000);
+            assert!(!contract.is_nonce_used(from, String::from("split")));
+        }
+
+        #[ink::test]
+        fn split_payment_charges_one_fee_on_the_total() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let seller = AccountId::from([0x04; 32]);
+            let affiliate = AccountId::from([0x05; 32]);
+            let marketplace = AccountId::from([0x06; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            // A per-share floor would charge 10 on each of the three shares
+            assert_eq!(contract.set_min_fee(10), Ok(()));
+            let fees_before = contract.balance_of(accounts.alice);
+
+            let recipients = vec![(seller, 100), (affiliate, 100), (marketplace, 100)];
+            let signature = sign_split(&payer, 300, "split", 1_000, &recipients);
+            assert_eq!(
+                contract.transfer_with_authorization_split(
+                    from,
+                    300,
+                    1_000,
+                    String::from("split"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                    recipients,
+                ),
+                Ok(())
+            );
+            assert_eq!(contract.balance_of(from), 100_000 - 300);
+            assert_eq!(contract.balance_of(accounts.alice), fees_before + 10);
+            assert_eq!(contract.get_total_fees(), 10);
+            assert_eq!(contract.balance_of(seller), 97);
+            assert_eq!(contract.balance_of(affiliate), 97);
+            assert_eq!(contract.balance_of(marketplace), 96);
+            let fees: Vec<Balance> = events_of::<TransferWithAuthorization>()
+                .iter()
+                .map(|event| event.facilitator_fee)
+                .collect();
+            assert_eq!(fees, vec![3, 3, 4]);
+        }
+
+        #[ink::test]
+        fn split_signature_commits_to_shares() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 0).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let seller = AccountId::from([0x04; 32]);
+            let affiliate = AccountId::from([0x05; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signed = vec![(seller, 9_000), (affiliate, 1_000)];
+            let signature = sign_split(&payer, 10_000, "split", 1_000, &signed);
+            assert_eq!(
+                contract.transfer_with_authorization_split(
+                    from,
+                    10_000,
+                    1_000,
+                    String::from("split"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                    vec![(seller, 1_000), (affiliate, 9_000)],
+                ),
+                Err(Error::InvalidSignature)
+            );
+        }
+
+        /// Signature topics of the events emitted by one sr25519 settlement
+        fn settlement_event_topics(fee_bps: u16) -> Vec<Vec<u8>> {
+            let mut contract = Httpusd::new(1_000_000_000_000, fee_bps).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let signature = sign_payment(&payer, to, 10_000, "events", 1_000);
+            let before = ink::env::test::recorded_events().count();
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    1_000,
+                    String::from("events"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            ink::env::test::recorded_events()
+                .skip(before)
+                .map(|event| event.topics[0].clone())
+                .collect()
+        }
+
+        #[ink::test]
+        fn zero_fee_settlement_emits_single_transfer() {
+            let topic = |signature: Option<[u8; 32]>| signature.unwrap().to_vec();
+            let mut expected = Vec::new();
+            #[cfg(feature = "debug-events")]
+            expected.push(topic(<DebugSignature as ink::env::Event>::SIGNATURE_TOPIC));
+            expected.extend([
+                topic(<NonceConsumed as ink::env::Event>::SIGNATURE_TOPIC),
+                topic(<Transfer as ink::env::Event>::SIGNATURE_TOPIC),
+                topic(<TransferWithAuthorization as ink::env::Event>::SIGNATURE_TOPIC),
+            ]);
+            assert_eq!(settlement_event_topics(0), expected);
+        }
+
+        #[ink::test]
+        fn fee_settlement_emits_net_then_fee_transfer() {
+            let topic = |signature: Option<[u8; 32]>| signature.unwrap().to_vec();
+            let mut expected = Vec::new();
+            #[cfg(feature = "debug-events")]
+            expected.push(topic(<DebugSignature as ink::env::Event>::SIGNATURE_TOPIC));
+            expected.extend([
+                topic(<NonceConsumed as ink::env::Event>::SIGNATURE_TOPIC),
+                topic(<Transfer as ink::env::Event>::SIGNATURE_TOPIC),
+                topic(<Transfer as ink::env::Event>::SIGNATURE_TOPIC),
+                topic(<TransferWithAuthorization as ink::env::Event>::SIGNATURE_TOPIC),
+            ]);
+            assert_eq!(settlement_event_topics(100), expected);
+        }
+
+        // The off-chain environment does not implement `set_code_hash`, so the
+        // owner path is covered by deployments rather than unit tests.
+        #[ink::test]
+        fn non_owner_cannot_set_code_hash() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000, 0).unwrap();
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.set_code_hash([0x42; 32]), Err(Error::NotOwner));
+            assert!(events_of::<CodeUpgraded>().is_empty());
+        }
+
+        #[ink::test]
+        fn last_settled_block_is_tracked() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.last_settled_block(from), None);
+
+            ink::env::test::set_block_number::<ink::env::DefaultEnvironment>(42);
+            let signature = sign_payment(&payer, to, 1_000, "block", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("block"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(contract.last_settled_block(from), Some(42));
+            assert_eq!(contract.last_settled_block(to), Some(42));
+        }
+
+        #[ink::test]
+        fn nonce_consumed_event_is_emitted() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            ink::env::test::set_block_number::<ink::env::DefaultEnvironment>(7);
+
+            let signature = sign_payment(&payer, to, 1_000, "observed", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("observed"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+
+            let consumed = events_of::<NonceConsumed>();
+            assert_eq!(consumed.len(), 1);
+            assert_eq!(consumed[0].from, from);
+            assert_eq!(
+                consumed[0].nonce_hash,
+                contract.compute_nonce_hash(&from, &String::from("observed"))
+            );
+            assert_eq!(consumed[0].block_number, 7);
+        }
+
+        #[ink::test]
+        fn nonce_consumed_event_precedes_failed_transfer() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+
+            assert_eq!(contract.transfer(from, 1_000), Ok(()));
+
+            // The recipient cannot be credited, so the transfer after nonce marking fails
+            contract.balances.insert(to, &Balance::MAX);
+            let signature = sign_payment(&payer, to, 1_000, "overflow", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("overflow"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::PSP22(PSP22Error::Overflow))
+            );
+            assert_eq!(events_of::<NonceConsumed>().len(), 1);
+        }
+
+        #[ink::test]
+        fn oversized_nonce_is_rejected_before_hashing() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let nonce = "n".repeat(MAX_NONCE_LEN + 1);
+            let signature = sign_payment(&payer, to, 1_000, &nonce, 1_000);
+            let events_before = ink::env::test::recorded_events().count();
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    nonce,
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::InvalidNonce)
+            );
+            // Signature verification (which records DebugSignature) never ran
+            assert_eq!(ink::env::test::recorded_events().count(), events_before);
+
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::new(),
+                    Vec::new(),
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::InvalidNonce)
+            );
+        }
+
+        #[ink::test]
+        fn zero_valid_until_is_expired_by_default() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+
+            let signature = sign_payment(&payer, to, 10_000, "forever", 0);
+            assert!(!contract.get_allow_no_expiry());
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    0,
+                    String::from("forever"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::PaymentExpired)
+            );
+        }
+
+        #[ink::test]
+        fn zero_valid_until_never_expires_when_allowed() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+
+            assert_eq!(contract.set_allow_no_expiry(true), Ok(()));
+            let signature = sign_payment(&payer, to, 10_000, "forever", 0);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    0,
+                    String::from("forever"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(contract.balance_of(to), 9_900);
+        }
+
+        #[ink::test]
+        fn fee_recipient_defaults_to_caller() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            assert_eq!(contract.get_fee_recipient(), accounts.alice);
+
+            let zero = AccountId::from([0u8; 32]);
+            let contract = Httpusd::new_with_fee_recipient(1_000_000_000_000, 100, zero).unwrap();
+            assert_eq!(contract.get_fee_recipient(), accounts.alice);
+
+            let contract =
+                Httpusd::new_with_fee_recipient(1_000_000_000_000, 100, accounts.charlie).unwrap();
+            assert_eq!(contract.get_fee_recipient(), accounts.charlie);
+        }
+
+        #[ink::test]
+        fn fees_land_in_fee_recipient() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.set_fee_recipient(accounts.charlie), Ok(()));
+
+            let signature = sign_payment(&payer, to, 10_000, "fee", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    1_000,
+                    String::from("fee"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(contract.balance_of(accounts.charlie), 100);
+            assert_eq!(contract.balance_of(to), 9_900);
+        }
+
+        #[ink::test]
+        fn skim_mode_pays_recipient_net_amount() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert_eq!(contract.get_fee_token(), None);
+
+            let signature = sign_payment(&payer, to, 10_000, "skim", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    10_000,
+                    1_000,
+                    String::from("skim"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+            assert_eq!(contract.balance_of(to), 9_900);
+            assert_eq!(contract.balance_of(from), 90_000);
+            assert_eq!(contract.get_total_fees(), 100);
+        }
+
+        #[ink::test]
+        fn fee_token_mode_pays_recipient_full_amount() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let from = AccountId::from([0x01; 32]);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 10_000), Ok(()));
+            assert_eq!(contract.set_fee_token(Some(accounts.django)), Ok(()));
+            assert_eq!(contract.get_fee_token(), Some(accounts.django));
+
+            let settlement = contract.prepare_settlement(from, to, 10_000, None).unwrap();
+            assert_eq!(settlement.net_amount, 10_000);
+            assert_eq!(settlement.facilitator_fee, 100);
+
+            assert_eq!(contract.set_fee_token(None), Ok(()));
+            let settlement = contract.prepare_settlement(from, to, 10_000, None).unwrap();
+            assert_eq!(settlement.net_amount, 9_900);
+            assert_eq!(settlement.facilitator_fee, 100);
+        }
+
+        #[ink::test]
+        fn typed_errors_for_overflow_and_non_owner() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            assert_eq!(contract.approve(accounts.bob, Balance::MAX), Ok(()));
+            assert_eq!(
+                contract.increase_allowance(accounts.bob, 1),
+                Err(Error::PSP22(PSP22Error::Overflow))
+            );
+            assert_eq!(
+                contract.mint(accounts.bob, Balance::MAX),
+                Err(Error::PSP22(PSP22Error::Overflow))
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.set_spend_limit_per_day(Some(1)), Err(Error::NotOwner));
+            // Fee changes are gated by FEE_MANAGER rather than ownership
+            assert_eq!(contract.set_facilitator_fee(50), Err(Error::MissingRole));
+        }
+
+        #[ink::test]
+        fn expiring_allowance_is_usable_only_until_expiry() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 0).unwrap();
+            assert_eq!(contract.approve_until(accounts.bob, 300, 1_000), Ok(()));
+            assert_eq!(contract.allowance_expiry(accounts.alice, accounts.bob), Some(1_000));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert_eq!(contract.transfer_from(accounts.alice, accounts.charlie, 100), Ok(()));
+            assert_eq!(contract.allowance(accounts.alice, accounts.bob), 200);
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_001);
+            assert_eq!(contract.allowance(accounts.alice, accounts.bob), 0);
+            assert_eq!(
+                contract.transfer_from(accounts.alice, accounts.charlie, 100),
+                Err(Error::PSP22(PSP22Error::InsufficientAllowance))
+            );
+            assert_eq!(contract.balance_of(accounts.charlie), 100);
+        }
+
+        #[ink::test]
+        fn approve_replaces_an_expiring_allowance() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 0).unwrap();
+            assert_eq!(contract.approve_until(accounts.bob, 300, 1_000), Ok(()));
+            assert_eq!(contract.approve(accounts.bob, 400), Ok(()));
+            assert_eq!(contract.allowance_expiry(accounts.alice, accounts.bob), None);
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(5_000);
+            assert_eq!(contract.allowance(accounts.alice, accounts.bob), 400);
+        }
+
+        #[ink::test]
+        fn increasing_an_expired_allowance_makes_it_spendable() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 0).unwrap();
+            assert_eq!(contract.approve_until(accounts.bob, 300, 1_000), Ok(()));
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_001);
+            assert_eq!(contract.increase_allowance(accounts.bob, 200), Ok(()));
+            assert_eq!(contract.allowance_expiry(accounts.alice, accounts.bob), None);
+            assert_eq!(contract.allowance(accounts.alice, accounts.bob), 200);
+            let approval = events_of::<Approval>().pop().unwrap();
+            assert_eq!(approval.value, 200);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.transfer_from(accounts.alice, accounts.charlie, 200), Ok(()));
+            assert_eq!(contract.balance_of(accounts.charlie), 200);
+            assert_eq!(contract.allowance(accounts.alice, accounts.bob), 0);
+        }
+
+        #[ink::test]
+        fn minting_near_max_balance_returns_overflow() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(Balance::MAX - 10, 0).unwrap();
+            assert_eq!(contract.mint(accounts.bob, 11), Err(Error::PSP22(PSP22Error::Overflow)));
+            assert_eq!(contract.total_supply(), Balance::MAX - 10);
+            assert_eq!(contract.balance_of(accounts.bob), 0);
+
+            assert_eq!(contract.mint(accounts.bob, 10), Ok(()));
+            assert_eq!(contract.total_supply(), Balance::MAX);
+            assert_eq!(
+                contract.burn(accounts.bob, 11),
+                Err(Error::PSP22(PSP22Error::InsufficientBalance))
+            );
+            assert_eq!(contract.burn(accounts.bob, 10), Ok(()));
+            assert_eq!(contract.total_supply(), Balance::MAX - 10);
+        }
+
+        #[ink::test]
+        fn overflowing_credit_leaves_both_balances_unchanged() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 0).unwrap();
+            contract.balances.insert(accounts.bob, &Balance::MAX);
+
+            assert_eq!(contract.transfer(accounts.bob, 1), Err(Error::PSP22(PSP22Error::Overflow)));
+            assert_eq!(contract.balance_of(accounts.alice), 1_000);
+            assert_eq!(contract.balance_of(accounts.bob), Balance::MAX);
+
+            // A self-transfer never overflows
+            assert_eq!(contract.transfer(accounts.alice, 1_000), Ok(()));
+            assert_eq!(contract.balance_of(accounts.alice), 1_000);
+        }
+
+        #[ink::test]
+        fn non_fee_manager_cannot_set_fee_token() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.set_fee_token(Some(accounts.django)), Err(Error::MissingRole));
+            assert_eq!(contract.get_fee_token(), None);
+        }
+
+        #[ink::test]
+        fn non_owner_cannot_set_fee_recipient() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.set_fee_recipient(accounts.bob), Err(Error::MissingRole));
+            assert_eq!(contract.get_fee_recipient(), accounts.alice);
+        }
+
+        #[ink::test]
+        fn namespace_gating_rejects_unregistered_prefixes() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+
+            let registered = String::from("app-a-1");
+            let prefix = contract.compute_nonce_hash(&from, &registered)[0];
+            let unregistered = (0..)
+                .map(|i| format!("app-b-{i}"))
+                .find(|nonce| contract.compute_nonce_hash(&from, nonce)[0] != prefix)
+                .unwrap();
+
+            assert_eq!(contract.set_namespace_gating(true), Ok(()));
+            assert_eq!(contract.register_namespace(prefix), Ok(()));
+            assert!(contract.is_namespace_registered(prefix));
+
+            let signature = sign_payment(&payer, to, 1_000, &unregistered, 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    unregistered,
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Err(Error::UnknownNamespace)
+            );
+
+            let signature = sign_payment(&payer, to, 1_000, &registered, 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    registered,
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn namespaces_are_ignored_without_gating() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            let payer = sr25519_keypair(0x11);
+            let from = sr25519_account(&payer);
+            let to = AccountId::from([0x03; 32]);
+            assert_eq!(contract.transfer(from, 100_000), Ok(()));
+            assert!(!contract.get_namespace_gating());
+
+            let signature = sign_payment(&payer, to, 1_000, "any-nonce", 1_000);
+            assert_eq!(
+                contract.transfer_with_authorization(
+                    from,
+                    to,
+                    1_000,
+                    1_000,
+                    String::from("any-nonce"),
+                    signature,
+                    SignatureScheme::Sr25519,
+                ),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn metadata_round_trips_through_constructor() {
+            let contract = Httpusd::new_with_metadata(
+                1_000_000,
+                100,
+                Some(String::from("HTTP USD")),
+                Some(String::from("HTTPUSD")),
+                6,
+            )
+            .unwrap();
+            assert_eq!(contract.token_name(), Some(String::from("HTTP USD")));
+            assert_eq!(contract.token_symbol(), Some(String::from("HTTPUSD")));
+            assert_eq!(contract.decimals(), 6);
+
+            assert_eq!(
+                Httpusd::new_with_metadata(1_000_000, 100, Some("x".repeat(65)), None, 6).err(),
+                Some(Error::MetadataTooLong)
+            );
+        }
+
+        #[ink::test]
+        fn default_constructor_keeps_12_decimals() {
+            let contract = Httpusd::new(1_000_000, 100).unwrap();
+            assert_eq!(contract.decimals(), 12);
+            assert_eq!(contract.token_name(), None);
+            assert_eq!(contract.token_symbol(), None);
+        }
+
+        #[ink::test]
+        fn two_step_ownership_transfer_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 100).unwrap();
+
+            assert_eq!(contract.transfer_ownership(accounts.bob), Ok(()));
+            assert_eq!(contract.get_pending_owner(), Some(accounts.bob));
+            assert_eq!(contract.get_owner(), accounts.alice);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.accept_ownership(), Ok(()));
+            assert_eq!(contract.get_owner(), accounts.bob);
+            assert_eq!(contract.get_pending_owner(), None);
+
+            let transferred = events_of::<OwnershipTransferred>();
+            assert_eq!(transferred.len(), 1);
+            assert_eq!(transferred[0].previous_owner, accounts.alice);
+            assert_eq!(transferred[0].new_owner, accounts.bob);
+
+            // The new owner can administer, the old one cannot
+            assert_eq!(contract.set_facilitator_fee(200), Ok(()));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(contract.set_facilitator_fee(300), Err(Error::MissingRole));
+        }
+
+        #[ink::test]
+        fn ownership_acceptance_by_wrong_caller_fails() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 100).unwrap();
+            assert_eq!(contract.transfer_ownership(accounts.bob), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(contract.accept_ownership(), Err(Error::NotPendingOwner));
+            assert_eq!(contract.get_owner(), accounts.alice);
+        }
+
+        #[ink::test]
+        fn pending_ownership_can_be_overwritten() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000, 100).unwrap();
+            assert_eq!(contract.transfer_ownership(accounts.bob), Ok(()));
+            assert_eq!(contract.transfer_ownership(accounts.charlie), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.accept_ownership(), Err(Error::NotPendingOwner));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(contract.accept_ownership(), Ok(()));
+            assert_eq!(contract.get_owner(), accounts.charlie);
+        }
+
+        #[ink::test]
+        fn renounced_ownership_locks_admin_functions() {
+            let mut contract = Httpusd::new(1_000, 100).unwrap();
+            assert_eq!(contract.renounce_ownership(), Ok(()));
+            assert_eq!(contract.get_owner(), AccountId::from([0u8; 32]));
+            assert_eq!(contract.set_facilitator_fee(200), Err(Error::MissingRole));
+        }
+
+        #[ink::test]
+        fn owner_can_update_metadata() {
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+            assert_eq!(contract.token_name(), None);
+            assert_eq!(contract.token_symbol(), None);
+
+            assert_eq!(contract.set_token_name(String::from("HTTP USD")), Ok(()));
+            assert_eq!(contract.set_token_symbol(String::from("HTTPUSD")), Ok(()));
+
+            assert_eq!(contract.token_name(), Some(String::from("HTTP USD")));
+            assert_eq!(contract.token_symbol(), Some(String::from("HTTPUSD")));
+        }
+
+        #[ink::test]
+        fn metadata_update_rejects_long_values_and_non_owner() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = Httpusd::new(1_000_000_000_000, 100).unwrap();
+
+            let too_long = "x".repeat(MAX_METADATA_LEN + 1);
+            assert_eq!(contract.set_token_name(too_long.clone()), Err(Error::MetadataTooLong));
+            assert_eq!(contract.set_token_symbol(too_long), Err(Error::MetadataTooLong));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.set_token_name(String::from("Other")), Err(Error::NotOwner));
+            assert_eq!(contract.token_name(), None);
+        }
+    }
+
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use ink_e2e::ContractsBackend;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        /// Bob's signature over a regular authorization from Bob
+        fn sign_as_bob(
+            domain_separator: [u8; 32],
+            to: AccountId,
+            amount: Balance,
+            nonce: &str,
+            valid_until: u64,
+        ) -> Vec<u8> {
+            use scale::Encode;
+            let from = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let mut message = Vec::new();
+            message.extend_from_slice(&from.encode());
+            message.extend_from_slice(&to.encode());
+            message.extend_from_slice(&amount.encode());
+            message.extend_from_slice(nonce.as_bytes());
+            message.extend_from_slice(&valid_until.encode());
+            message.extend_from_slice(&domain_separator);
+            let mut hash = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(&message, &mut hash);
+            ink_e2e::bob().sign(&hash).0.to_vec()
+        }
+
+        #[ink_e2e::test]
+        async fn fee_is_pulled_through_the_fee_token<Client: E2EBackend>(
+            mut client: Client,
+        ) -> E2EResult<()> {
+            // A second, fee-free Httpusd instance serves as the PSP22 fee token
+            let mut constructor = HttpusdRef::new(1_000_000_000_000, 0);
+            let fee_token = client
+                .instantiate("httpusd", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("fee token instantiate failed");
+            let mut token = fee_token.call_builder::<Httpusd>();
+            let mut constructor = HttpusdRef::new(1_000_000_000_000, 100);
+            let httpusd = client
+                .instantiate("httpusd", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("httpusd instantiate failed");
+            let mut contract = httpusd.call_builder::<Httpusd>();
+
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let to = AccountId::from([0x03; 32]);
+            let set_fee_token = contract.set_fee_token(Some(fee_token.account_id));
+            client
+                .call(&ink_e2e::alice(), &set_fee_token)
+                .submit()
+                .await
+                .expect("set_fee_token failed");
+            client
+                .call(&ink_e2e::alice(), &contract.transfer(bob, 100_000))
+                .submit()
+                .await
+                .expect("funding payer failed");
+            client
+                .call(&ink_e2e::alice(), &token.transfer(bob, 1_000))
+                .submit()
+                .await
+                .expect("funding payer's fee balance failed");
+
+            let domain_separator = client
+                .call(&ink_e2e::alice(), &contract.domain_separator())
+                .dry_run()
+                .await?
+                .return_value();
+            let signature = sign_as_bob(domain_separator, to, 10_000, "fee-token", u64::MAX);
+            let settle = contract.transfer_with_authorization(
+                bob,
+                to,
+                10_000,
+                u64::MAX,
+                String::from("fee-token"),
+                signature,
+                SignatureScheme::Sr25519,
+            );
+
+            // Without an allowance on the fee token the whole settlement reverts
+            let dry_run = client.call(&ink_e2e::alice(), &settle).dry_run().await?;
+            assert_eq!(dry_run.return_value(), Err(Error::FeeTokenTransferFailed));
+            assert!(client.call(&ink_e2e::alice(), &settle).submit().await.is_err());
+            let balance_of_to = contract.balance_of(to);
+            let balance = client.call(&ink_e2e::alice(), &balance_of_to).dry_run().await?;
+            assert_eq!(balance.return_value(), 0);
+            let nonce_used = contract.is_nonce_used(bob, String::from("fee-token"));
+            let used = client.call(&ink_e2e::alice(), &nonce_used).dry_run().await?;
+            assert!(!used.return_value());
+
+            // Once Httpusd may spend the payer's fee token, the same authorization
+            // settles: the recipient gets the full amount and the 1% fee moves on
+            // the fee token
+            client
+                .call(&ink_e2e::bob(), &token.approve(httpusd.account_id, 100))
+                .submit()
+                .await
+                .expect("approve failed");
+            client
+                .call(&ink_e2e::alice(), &settle)
+                .submit()
+                .await
+                .expect("settlement failed");
+
+            let balance = client.call(&ink_e2e::alice(), &balance_of_to).dry_run().await?;
+            assert_eq!(balance.return_value(), 10_000);
+            let balance_of_bob = contract.balance_of(bob);
+            let balance = client.call(&ink_e2e::alice(), &balance_of_bob).dry_run().await?;
+            assert_eq!(balance.return_value(), 90_000);
+            let fee_balance_of_bob = token.balance_of(bob);
+            let balance = client.call(&ink_e2e::alice(), &fee_balance_of_bob).dry_run().await?;
+            assert_eq!(balance.return_value(), 900);
+            let fee_balance_of_alice = token.balance_of(alice);
+            let balance = client.call(&ink_e2e::alice(), &fee_balance_of_alice).dry_run().await?;
+            assert_eq!(balance.return_value(), 1_000_000_000_000 - 1_000 + 100);
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn payment_hook_runs_within_its_gas_budget<Client: E2EBackend>(
+            mut client: Client,
+        ) -> E2EResult<()> {
+            use payment_hook::{PaymentHook, PaymentHookRef};
+
+            let mut constructor = HttpusdRef::new(2_000_000_000_000, 0);
+            let httpusd = client
+                .instantiate("httpusd", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("httpusd instantiate failed");
+            let mut contract = httpusd.call_builder::<Httpusd>();
+            let mut constructor = PaymentHookRef::new(false);
+            let hook = client
+                .instantiate("payment_hook", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("hook instantiate failed");
+            let mut constructor = PaymentHookRef::new(true);
+            let greedy_hook = client
+                .instantiate("payment_hook", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("greedy hook instantiate failed");
+
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let charlie = ink_e2e::account_id(ink_e2e::AccountKeyring::Charlie);
+            let dave = ink_e2e::account_id(ink_e2e::AccountKeyring::Dave);
+            client
+                .call(&ink_e2e::alice(), &contract.transfer(bob, 100_000))
+                .submit()
+                .await
+                .expect("funding payer failed");
+            client
+                .call(&ink_e2e::charlie(), &contract.set_payment_hook(Some(hook.account_id)))
+                .submit()
+                .await
+                .expect("set_payment_hook failed");
+            let set_greedy_hook = contract.set_payment_hook(Some(greedy_hook.account_id));
+            client
+                .call(&ink_e2e::dave(), &set_greedy_hook)
+                .submit()
+                .await
+                .expect("set_payment_hook failed");
+
+            let domain_separator = client
+                .call(&ink_e2e::alice(), &contract.domain_separator())
+                .dry_run()
+                .await?
+                .return_value();
+            for (to, nonce) in [(charlie, "hooked"), (dave, "greedy")] {
+                let signature = sign_as_bob(domain_separator, to, 10_000, nonce, u64::MAX);
+                let settle = contract.transfer_with_authorization(
+                    bob,
+                    to,
+                    10_000,
+                    u64::MAX,
+                    String::from(nonce),
+                    signature,
+                    SignatureScheme::Sr25519,
+                );
+                client
+                    .call(&ink_e2e::alice(), &settle)
+                    .submit()
+                    .await
+                    .expect("settlement failed");
+                let balance_of_to = contract.balance_of(to);
+                let balance = client.call(&ink_e2e::alice(), &balance_of_to).dry_run().await?;
+                assert_eq!(balance.return_value(), 10_000);
+            }
+
+            // The well-behaved hook was notified; the greedy one ran out of its
+            // own budget, was rolled back, and the payment to Dave still settled
+            let received = hook.call_builder::<PaymentHook>().received();
+            let notified = client.call(&ink_e2e::alice(), &received).dry_run().await?;
+            assert_eq!(notified.return_value(), (1, 10_000));
+            let received = greedy_hook.call_builder::<PaymentHook>().received();
+            let notified = client.call(&ink_e2e::alice(), &received).dry_run().await?;
+            assert_eq!(notified.return_value(), (0, 0));
+            Ok(())
         }
     }
 }
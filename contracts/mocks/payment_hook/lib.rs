@@ -0,0 +1,51 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::payment_hook::{PaymentHook, PaymentHookRef};
+
+/// payment_hook - recipient hook used by the httpusd e2e tests
+///
+/// Counts the settlements it is notified of, or burns gas forever when
+/// constructed with `greedy` to exercise httpusd's hook gas limits.
+#[ink::contract]
+mod payment_hook {
+    use ink::prelude::string::String;
+
+    #[ink(storage)]
+    pub struct PaymentHook {
+        /// Spin until out of gas instead of recording the payment
+        greedy: bool,
+        /// Number of settlements notified
+        calls: u32,
+        /// Sum of net amounts notified
+        received: Balance,
+    }
+
+    impl PaymentHook {
+        #[ink(constructor)]
+        pub fn new(greedy: bool) -> Self {
+            Self {
+                greedy,
+                calls: 0,
+                received: 0,
+            }
+        }
+
+        /// Called by httpusd after a settlement to the registering recipient
+        #[ink(message)]
+        pub fn on_payment_received(&mut self, _from: AccountId, amount: Balance, _nonce: String) {
+            if self.greedy {
+                loop {
+                    self.calls = self.calls.wrapping_add(1);
+                }
+            }
+            self.calls = self.calls.saturating_add(1);
+            self.received = self.received.saturating_add(amount);
+        }
+
+        /// Number of settlements notified and their total net amount
+        #[ink(message)]
+        pub fn received(&self) -> (u32, Balance) {
+            (self.calls, self.received)
+        }
+    }
+}
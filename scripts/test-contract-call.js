@@ -2,6 +2,7 @@ import { ApiPromise, WsProvider } from '@polkadot/api';
 import { ContractPromise } from '@polkadot/api-contract';
 import { Keyring } from '@polkadot/keyring';
 import { u8aConcat, stringToU8a, bnToU8a, u8aToHex, hexToU8a } from '@polkadot/util';
+import { blake2AsU8a, decodeAddress } from '@polkadot/util-crypto';
 import * as fs from 'fs';
 
 const WS_ENDPOINT = 'ws://localhost:9944';
@@ -33,7 +34,17 @@ async function main() {
   const amountBytes = bnToU8a(amount, { bitLength: 128, isLe: true });
   const nonceBytes = stringToU8a(nonce);
   const validUntilBytes = bnToU8a(validUntil, { bitLength: 64, isLe: true });
-  const message = u8aConcat(from, to, amountBytes, nonceBytes, validUntilBytes);
+  // Domain separator for this contract instance (chain_id 0)
+  const domainSeparator = blake2AsU8a(
+    u8aConcat(
+      stringToU8a('polkax402:httpusd'),
+      new Uint8Array([1]),
+      bnToU8a(0, { bitLength: 32, isLe: true }),
+      decodeAddress(CONTRACT_ADDRESS)
+    ),
+    256
+  );
+  const message = u8aConcat(from, to, amountBytes, nonceBytes, validUntilBytes, domainSeparator);
 
   console.log('Payment details:');
   console.log('  From:', bob.address);
@@ -44,7 +55,7 @@ async function main() {
   console.log();
 
   // Sign
-  const signature = bob.sign(message);
+  const signature = bob.sign(blake2AsU8a(message, 256));
   const signatureHex = `0x${Buffer.from(signature).toString('hex')}`;
 
   console.log('Signature:');
@@ -68,7 +79,8 @@ async function main() {
     amount.toString(),// amount
     validUntil,       // validUntil
     nonce,            // nonce
-    signatureHex      // signature as hex string
+    signatureHex,     // signature as hex string
+    'Sr25519'         // signature scheme
   );
 
   const result = await new Promise((resolve, reject) => {
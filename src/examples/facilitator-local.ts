@@ -36,6 +36,7 @@ interface PaymentRequest {
   nonce: string;
   validUntil: number;
   signature: string;
+  scheme?: 'Sr25519' | 'Ecdsa';
   network?: string;
 }
 
@@ -169,7 +170,8 @@ async function main() {
         payment.amount,
         payment.validUntil,
         payment.nonce,
-        payment.signature
+        payment.signature,
+        payment.scheme ?? 'Sr25519'
       );
 
       // Sign and send transaction
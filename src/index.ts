@@ -16,6 +16,8 @@ export {
 // Re-export utilities
 export {
   generateNonce,
+  computeDomainSeparator,
+  buildPaymentMessage,
   createPaymentPayload,
   signPaymentPayload,
   createX402Payment,
@@ -30,6 +32,7 @@ export type {
   PolkadotNetwork,
   PolkadotPaymentPayload,
   PolkadotSignedPayment,
+  SignatureScheme,
   X402PolkadotPayment,
   X402PaymentRequired,
   PolkadotSigner,
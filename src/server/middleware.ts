@@ -349,6 +349,7 @@ export function createX402Middleware(config: X402MiddlewareConfig) {
             body: JSON.stringify({
               ...parsedPayload,
               signature: payment.payload.signature,
+              scheme: payment.payload.scheme ?? 'Sr25519',
               network: payment.network,
             }),
           });
@@ -25,9 +25,17 @@ export interface PolkadotPaymentPayload {
   amount: string;        // Amount in smallest unit (planck)
   nonce: string;         // Random nonce for replay protection
   validUntil: number;    // Unix timestamp (ms) when payment expires
-  asset?: string;        // Optional asset ID for non-native tokens
+  asset?: string;        // HTTPUSD contract address; required to sign and verify
+  chainId?: number;      // Contract chain_id (default: 0)
+  epoch?: number;        // Payer's authorization_epoch (default: 0)
 }
 
+/**
+ * Signature scheme of a payment authorization, matching the contract's
+ * SignatureScheme enum
+ */
+export type SignatureScheme = 'Sr25519' | 'Ecdsa';
+
 /**
  * Signed payment authorization
  */
@@ -35,6 +43,7 @@ export interface PolkadotSignedPayment {
   payload: string;       // JSON-stringified PaymentPayload
   signature: string;     // Hex-encoded signature
   signerPublicKey?: string; // Optional public key for verification
+  scheme?: SignatureScheme; // Scheme of `signature` (default: 'Sr25519')
 }
 
 /**
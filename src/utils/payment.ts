@@ -3,8 +3,8 @@
  */
 
 import { cryptoWaitReady } from '@polkadot/util-crypto';
-import { stringToU8a, u8aToHex } from '@polkadot/util';
-import { randomAsU8a } from '@polkadot/util-crypto';
+import { bnToU8a, stringToU8a, u8aConcat, u8aToHex } from '@polkadot/util';
+import { blake2AsU8a, decodeAddress, randomAsU8a } from '@polkadot/util-crypto';
 import type {
   PolkadotPaymentPayload,
   PolkadotSignedPayment,
@@ -14,6 +14,13 @@ import type {
   PolkadotNetwork,
 } from '../types/index.js';
 
+/**
+ * Domain separator inputs, matching DOMAIN_PERSONALIZATION and DOMAIN_VERSION
+ * in the contract
+ */
+const DOMAIN_PERSONALIZATION = 'polkax402:httpusd';
+const DOMAIN_VERSION = 1;
+
 /**
  * Ensure crypto library is ready
  */
@@ -34,8 +41,56 @@ export function generateNonce(): string {
   return Buffer.from(bytes).toString('hex');
 }
 
+/**
+ * Compute the contract's domain separator:
+ * Blake2-256(DOMAIN_PERSONALIZATION ++ DOMAIN_VERSION ++ chain_id (u32 LE) ++ contract)
+ */
+export function computeDomainSeparator(contractAddress: string, chainId: number = 0): Uint8Array {
+  return blake2AsU8a(
+    u8aConcat(
+      stringToU8a(DOMAIN_PERSONALIZATION),
+      new Uint8Array([DOMAIN_VERSION]),
+      bnToU8a(chainId, { bitLength: 32, isLe: true }),
+      decodeAddress(contractAddress)
+    ),
+    256
+  );
+}
+
+/**
+ * Build the message a payment authorization signs
+ *
+ * Matches the contract's payment_message:
+ * from ++ to ++ amount (u128 LE) ++ nonce (UTF-8) ++ valid_until (u64 LE)
+ * ++ domain_separator [++ epoch (u32 LE), only when nonzero]
+ */
+export function buildPaymentMessage(payload: PolkadotPaymentPayload): Uint8Array {
+  if (!payload.asset) {
+    throw new Error('Payment payload has no asset (contract address) to bind the signature to');
+  }
+
+  const parts = [
+    decodeAddress(payload.from),
+    decodeAddress(payload.to),
+    bnToU8a(BigInt(payload.amount), { bitLength: 128, isLe: true }),
+    // Nonce as raw bytes (contract uses nonce.as_bytes())
+    stringToU8a(payload.nonce),
+    bnToU8a(payload.validUntil, { bitLength: 64, isLe: true }),
+    computeDomainSeparator(payload.asset, payload.chainId ?? 0),
+  ];
+  if (payload.epoch) {
+    parts.push(bnToU8a(payload.epoch, { bitLength: 32, isLe: true }));
+  }
+  return u8aConcat(...parts);
+}
+
 /**
  * Create a payment payload from payment requirements
+ *
+ * The chain id and the payer's authorization epoch are taken from
+ * `paymentRequired.extra.chainId` and `paymentRequired.extra.epoch` when the
+ * server provides them. A nonzero epoch is appended to the signed message, so
+ * payers whose epoch was bumped need it to produce a valid signature.
  */
 export function createPaymentPayload(
   from: string,
@@ -52,14 +107,16 @@ export function createPaymentPayload(
     nonce,
     validUntil,
     asset: paymentRequired.asset,
+    chainId: paymentRequired.extra?.chainId,
+    epoch: paymentRequired.extra?.epoch,
   };
 }
 
 /**
  * Sign a payment payload using a Polkadot signer
  *
- * This creates the same message format that the contract expects:
- * SCALE(from, to, amount, nonce, valid_until)
+ * Signs `buildPaymentMessage(payload)`, the same message the contract's
+ * payment_message builds.
  */
 export async function signPaymentPayload(
   payload: PolkadotPaymentPayload,
@@ -67,44 +124,13 @@ export async function signPaymentPayload(
 ): Promise<PolkadotSignedPayment> {
   await ensureCryptoReady();
 
-  const { encodeAddress, decodeAddress } = await import('@polkadot/util-crypto');
-  const { u8aConcat, stringToU8a, bnToU8a, u8aToHex, hexToU8a } = await import('@polkadot/util');
-
-  // SCALE encode the payload to match contract's expected format
-  // Contract expects: from + to + amount + nonce + valid_until (all SCALE encoded)
-  const fromBytes = decodeAddress(payload.from);
-  const toBytes = decodeAddress(payload.to);
-
-  // Amount as u128 (16 bytes, little-endian)
-  const amountBn = BigInt(payload.amount);
-  const amountBytes = bnToU8a(amountBn, { bitLength: 128, isLe: true });
-
-  // Nonce as raw bytes (contract uses nonce.as_bytes())
-  // In Rust, String.as_bytes() returns UTF-8 bytes, so "0x1a2b..." -> bytes for '0','x','1','a'...
-  const nonceBytes = stringToU8a(payload.nonce);
-
-  // validUntil as u64 (8 bytes, little-endian)
-  const validUntilBytes = bnToU8a(payload.validUntil, { bitLength: 64, isLe: true });
-
-  // Concatenate all parts (this matches the contract's verify_signature logic)
-  const message = u8aConcat(
-    fromBytes,
-    toBytes,
-    amountBytes,
-    nonceBytes,
-    validUntilBytes
-  );
+  const message = buildPaymentMessage(payload);
 
   // Debug: Log the message being signed
   console.log('DEBUG: Signing message:');
-  console.log('  from bytes:', u8aToHex(fromBytes));
-  console.log('  to bytes:', u8aToHex(toBytes));
-  console.log('  amount bytes:', u8aToHex(amountBytes));
-  console.log('  nonce bytes:', u8aToHex(nonceBytes));
-  console.log('  validUntil bytes:', u8aToHex(validUntilBytes));
   console.log('  full message:', u8aToHex(message));
 
-  // Sign the raw message (KeyringPair.sign will hash it with Blake2-256 automatically)
+  // The signer hashes with Blake2-256 before signing (see createPolkadotSigner)
   const result = await signer.sign(message);
   console.log('  signature:', result.signature);
   console.log('  signature length:', result.signature.length);
@@ -116,6 +142,7 @@ export async function signPaymentPayload(
     payload: payloadString,
     signature: result.signature,
     signerPublicKey: signer.address,
+    scheme: 'Sr25519',
   };
 }
 
@@ -203,8 +230,7 @@ export async function verifyPaymentSignature(
   payment: X402PolkadotPayment
 ): Promise<boolean> {
   try {
-    const { blake2AsU8a, decodeAddress, signatureVerify } = await import('@polkadot/util-crypto');
-    const { u8aConcat, stringToU8a, bnToU8a } = await import('@polkadot/util');
+    const { signatureVerify } = await import('@polkadot/util-crypto');
 
     // Ensure crypto is ready AFTER imports
     await ensureCryptoReady();
@@ -218,21 +244,11 @@ export async function verifyPaymentSignature(
     // Parse the payload to reconstruct the signed message
     const parsedPayload: PolkadotPaymentPayload = JSON.parse(payload);
 
-    // Reconstruct the SCALE-encoded message that was signed
-    // This must match exactly what the client signed in signPaymentPayload()
-    const fromBytes = decodeAddress(parsedPayload.from);
-    const toBytes = decodeAddress(parsedPayload.to);
-    const amountBytes = bnToU8a(BigInt(parsedPayload.amount), { bitLength: 128, isLe: true });
-    const nonceBytes = stringToU8a(parsedPayload.nonce);
-    const validUntilBytes = bnToU8a(parsedPayload.validUntil, { bitLength: 64, isLe: true });
-
-    const message = u8aConcat(
-      fromBytes,
-      toBytes,
-      amountBytes,
-      nonceBytes,
-      validUntilBytes
-    );
+    // Reconstruct the message that was signed, exactly as signPaymentPayload() did
+    const message = buildPaymentMessage({
+      ...parsedPayload,
+      asset: parsedPayload.asset ?? payment.asset,
+    });
 
     // Hash the message with Blake2-256 (same as client)
     const hash = blake2AsU8a(message, 256);